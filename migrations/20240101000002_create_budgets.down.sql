@@ -0,0 +1,4 @@
+-- Down: Drop budget_alerts, trigger, and budgets
+DROP TABLE IF EXISTS budget_alerts;
+DROP TRIGGER IF EXISTS trg_budgets_updated_at ON budgets;
+DROP TABLE IF EXISTS budgets;
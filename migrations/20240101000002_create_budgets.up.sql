@@ -0,0 +1,26 @@
+-- Up: Create budgets and budget_alerts (PostgreSQL)
+CREATE TABLE budgets (
+    id SERIAL PRIMARY KEY,
+    bucket TEXT NOT NULL,
+    period TEXT NOT NULL CHECK (period IN ('weekly', 'monthly')),
+    amount REAL NOT NULL CHECK (amount > 0),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (bucket, period)
+);
+
+CREATE TRIGGER trg_budgets_updated_at
+BEFORE UPDATE ON budgets
+FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- One row per threshold crossed per budget period, so each threshold alerts once
+CREATE TABLE budget_alerts (
+    id SERIAL PRIMARY KEY,
+    budget_id INTEGER NOT NULL,
+    period_start DATE NOT NULL,
+    threshold INTEGER NOT NULL,
+    spent REAL NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (budget_id, period_start, threshold),
+    FOREIGN KEY (budget_id) REFERENCES budgets(id) ON DELETE CASCADE
+);
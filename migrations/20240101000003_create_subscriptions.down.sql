@@ -0,0 +1,3 @@
+-- Down: Drop trigger and subscriptions
+DROP TRIGGER IF EXISTS trg_subscriptions_updated_at ON subscriptions;
+DROP TABLE IF EXISTS subscriptions;
@@ -0,0 +1,20 @@
+-- Up: Create subscriptions detected from recurring transactions (PostgreSQL)
+CREATE TABLE subscriptions (
+    id SERIAL PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    merchant TEXT NOT NULL,
+    description TEXT NOT NULL,
+    cadence TEXT NOT NULL CHECK (cadence IN ('weekly', 'monthly', 'annual')),
+    expected_amount REAL NOT NULL,
+    occurrences INTEGER NOT NULL,
+    last_seen_date DATE NOT NULL,
+    next_expected_date DATE NOT NULL,
+    status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'missed')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (account_id, merchant)
+);
+
+CREATE TRIGGER trg_subscriptions_updated_at
+BEFORE UPDATE ON subscriptions
+FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
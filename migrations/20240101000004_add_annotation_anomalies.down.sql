@@ -0,0 +1,2 @@
+-- Down: Drop anomaly flags from transaction_annotations
+ALTER TABLE transaction_annotations DROP COLUMN IF EXISTS anomalies;
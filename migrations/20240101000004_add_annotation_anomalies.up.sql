@@ -0,0 +1,3 @@
+-- Up: Track anomaly flags raised for a transaction on its annotation
+ALTER TABLE transaction_annotations
+    ADD COLUMN anomalies TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,5 @@
+-- Down: Drop sweep plans, trigger, and sweep rules
+DROP TABLE IF EXISTS sweep_plan_items;
+DROP TABLE IF EXISTS sweep_plans;
+DROP TRIGGER IF EXISTS trg_sweep_rules_updated_at ON sweep_rules;
+DROP TABLE IF EXISTS sweep_rules;
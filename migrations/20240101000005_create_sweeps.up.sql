@@ -0,0 +1,45 @@
+-- Up: Create sweep rules and sweep plans awaiting approval (PostgreSQL)
+CREATE TABLE sweep_rules (
+    id SERIAL PRIMARY KEY,
+    kind TEXT NOT NULL CHECK (kind IN ('underspend', 'round_up')),
+    bucket TEXT NOT NULL,
+    from_account_id TEXT NOT NULL,
+    to_account_id TEXT NOT NULL,
+    round_to REAL CHECK (round_to IS NULL OR round_to > 0),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CHECK (kind <> 'round_up' OR round_to IS NOT NULL)
+);
+
+CREATE TRIGGER trg_sweep_rules_updated_at
+BEFORE UPDATE ON sweep_rules
+FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- One plan per period; nothing is transferred until a plan is approved
+CREATE TABLE sweep_plans (
+    id SERIAL PRIMARY KEY,
+    period_start DATE NOT NULL UNIQUE,
+    period_end DATE NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'executed', 'failed')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    approved_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE TABLE sweep_plan_items (
+    id SERIAL PRIMARY KEY,
+    plan_id INTEGER NOT NULL,
+    rule_id INTEGER,
+    from_account_id TEXT NOT NULL,
+    to_account_id TEXT NOT NULL,
+    amount REAL NOT NULL CHECK (amount > 0),
+    reference TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'executed', 'failed')),
+    payment_reference TEXT,
+    error TEXT,
+    executed_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (plan_id) REFERENCES sweep_plans(id) ON DELETE CASCADE,
+    FOREIGN KEY (rule_id) REFERENCES sweep_rules(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_sweep_plan_items_plan_id ON sweep_plan_items(plan_id);
@@ -0,0 +1,2 @@
+-- Down: Drop card_event_id
+ALTER TABLE investec_transactions DROP COLUMN IF EXISTS card_event_id;
@@ -0,0 +1,4 @@
+-- Up: Track transactions received from Programmable Banking card events
+-- Pending card events are stored without a uuid and matched to the posted
+-- transaction by the next sync
+ALTER TABLE investec_transactions ADD COLUMN card_event_id TEXT UNIQUE;
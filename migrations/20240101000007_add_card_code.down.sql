@@ -0,0 +1,3 @@
+-- Down: Drop card code tables
+DROP TABLE IF EXISTS card_code_deployments;
+DROP TABLE IF EXISTS card_rules;
@@ -0,0 +1,21 @@
+-- Up: Create card rules and the history of code deployed to programmable cards
+-- Purchases at a rule's merchant category are declined once the bucket's budget runs out
+CREATE TABLE card_rules (
+    id SERIAL PRIMARY KEY,
+    bucket TEXT NOT NULL,
+    merchant_category_code TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (bucket, merchant_category_code)
+);
+
+-- Every version saved to a card, numbered per card
+CREATE TABLE card_code_deployments (
+    id SERIAL PRIMARY KEY,
+    card_key TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    code_id TEXT NOT NULL,
+    code TEXT NOT NULL,
+    published BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (card_key, version)
+);
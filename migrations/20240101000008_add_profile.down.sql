@@ -0,0 +1,3 @@
+-- Down: Drop the transaction profile
+DROP INDEX IF EXISTS idx_investec_transactions_profile;
+ALTER TABLE investec_transactions DROP COLUMN IF EXISTS profile;
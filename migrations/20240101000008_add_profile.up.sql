@@ -0,0 +1,5 @@
+-- Up: Tag transactions with the Investec profile they were synced from
+ALTER TABLE investec_transactions
+    ADD COLUMN profile TEXT NOT NULL DEFAULT 'default';
+
+CREATE INDEX idx_investec_transactions_profile ON investec_transactions(profile);
@@ -0,0 +1,2 @@
+-- Down: Drop scheduled job status
+DROP TABLE IF EXISTS scheduled_jobs;
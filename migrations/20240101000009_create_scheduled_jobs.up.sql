@@ -0,0 +1,18 @@
+-- Up: Track the last and next run of every scheduled job
+CREATE TABLE scheduled_jobs (
+    name TEXT PRIMARY KEY,
+    schedule TEXT NOT NULL,
+    -- running, succeeded or failed
+    status TEXT,
+    last_started_at TIMESTAMP WITH TIME ZONE,
+    last_finished_at TIMESTAMP WITH TIME ZONE,
+    last_error TEXT,
+    next_run_at TIMESTAMP WITH TIME ZONE,
+    -- Runs skipped because the previous one was still going
+    skipped_runs INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER trg_scheduled_jobs_updated_at
+BEFORE UPDATE ON scheduled_jobs
+FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
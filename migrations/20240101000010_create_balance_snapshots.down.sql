@@ -0,0 +1,2 @@
+-- Down: Drop balance_snapshots
+DROP TABLE IF EXISTS balance_snapshots;
@@ -0,0 +1,12 @@
+-- Up: Create balance_snapshots, one row per account each time balances are recorded
+CREATE TABLE balance_snapshots (
+    id SERIAL PRIMARY KEY,
+    profile TEXT NOT NULL,
+    account_id TEXT NOT NULL,
+    current_balance DOUBLE PRECISION NOT NULL,
+    available_balance DOUBLE PRECISION NOT NULL,
+    currency TEXT NOT NULL,
+    taken_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_balance_snapshots_account ON balance_snapshots(account_id, taken_at);
@@ -0,0 +1,2 @@
+-- Down: Drop last_succeeded_at
+ALTER TABLE scheduled_jobs DROP COLUMN IF EXISTS last_succeeded_at;
@@ -0,0 +1,2 @@
+-- Up: Remember the last successful run of each job, for readiness checks
+ALTER TABLE scheduled_jobs ADD COLUMN last_succeeded_at TIMESTAMP WITH TIME ZONE;
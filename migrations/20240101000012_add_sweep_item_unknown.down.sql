@@ -0,0 +1,5 @@
+-- Down: Disallow the unknown sweep transfer status
+UPDATE sweep_plan_items SET status = 'failed' WHERE status = 'unknown';
+ALTER TABLE sweep_plan_items DROP CONSTRAINT sweep_plan_items_status_check;
+ALTER TABLE sweep_plan_items ADD CONSTRAINT sweep_plan_items_status_check
+    CHECK (status IN ('pending', 'executed', 'failed'));
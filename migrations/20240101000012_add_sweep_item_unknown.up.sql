@@ -0,0 +1,5 @@
+-- Up: Allow sweep transfers whose outcome is unknown (e.g. timed out), so they
+-- aren't retried until confirmed by hand
+ALTER TABLE sweep_plan_items DROP CONSTRAINT sweep_plan_items_status_check;
+ALTER TABLE sweep_plan_items ADD CONSTRAINT sweep_plan_items_status_check
+    CHECK (status IN ('pending', 'executed', 'failed', 'unknown'));
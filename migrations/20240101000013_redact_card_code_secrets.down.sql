@@ -0,0 +1,2 @@
+-- Down: Redacted secrets can't be restored
+SELECT 1;
@@ -0,0 +1,6 @@
+-- Up: Redact webhook secrets written into stored card code versions
+-- Forwarding code used to embed the secret; it is now read from the card's
+-- environment variables
+UPDATE card_code_deployments
+SET code = REGEXP_REPLACE(code, '"secret": "([^"\\]|\\.)*"', '"secret": "***"', 'g')
+WHERE code LIKE '%"secret": "%';
@@ -0,0 +1,2 @@
+-- Down: Drop occurred_at
+ALTER TABLE investec_transactions DROP COLUMN IF EXISTS occurred_at;
@@ -0,0 +1,3 @@
+-- Up: Add when a card event said the purchase happened
+-- Synced transactions only carry a date; kept after the event is reconciled
+ALTER TABLE investec_transactions ADD COLUMN occurred_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,357 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use chrono::{DateTime, Days, FixedOffset, NaiveDate, NaiveDateTime, Timelike, Utc};
+use sqlx::PgPool;
+
+use crate::db;
+use crate::notifications::ClassifiedTransaction;
+use crate::recurring::normalize_merchant;
+
+/// How far back history is considered when scoring new transactions.
+const HISTORY_DAYS: u64 = 365;
+/// Standard deviations from the mean before an amount is unusual.
+const Z_SCORE_THRESHOLD: f64 = 3.0;
+const MIN_MERCHANT_SAMPLES: usize = 5;
+const MIN_BUCKET_SAMPLES: usize = 10;
+/// Prior transactions an account needs before new merchants are flagged, so
+/// the first sync does not flag everything.
+const MIN_ACCOUNT_HISTORY: usize = 20;
+const DUPLICATE_WINDOW_MINUTES: i64 = 10;
+/// Hours (local to the bank feed) considered unusual for card activity.
+const UNUSUAL_HOURS: std::ops::Range<u32> = 0..5;
+/// Card events are timestamped in UTC; hours are judged in South African
+/// time (UTC+2, no daylight saving).
+const FEED_UTC_OFFSET_SECS: i32 = 2 * 60 * 60;
+/// Share of an account's timed history allowed in unusual hours before such
+/// activity is treated as normal for that account.
+const UNUSUAL_HOUR_RATIO: f64 = 0.05;
+
+const FOREIGN_CURRENCIES: [&str; 10] = [
+    "usd", "eur", "gbp", "aud", "cad", "chf", "jpy", "cny", "aed", "inr",
+];
+const FOREIGN_COUNTRY_SUFFIXES: [&str; 12] = [
+    "us", "gb", "uk", "nl", "ie", "de", "fr", "lu", "sg", "hk", "cn", "ae",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AnomalyKind {
+    UnusualAmount,
+    NewMerchant,
+    ForeignDescription,
+    DuplicateCharge,
+    UnusualHour,
+}
+
+impl AnomalyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyKind::UnusualAmount => "unusual_amount",
+            AnomalyKind::NewMerchant => "new_merchant",
+            AnomalyKind::ForeignDescription => "foreign_description",
+            AnomalyKind::DuplicateCharge => "duplicate_charge",
+            AnomalyKind::UnusualHour => "unusual_hour",
+        }
+    }
+}
+
+/// A stored debit used as history when scoring.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AnomalyHistoryRow {
+    pub id: i32,
+    pub account_id: String,
+    pub description: String,
+    pub bucket: Option<String>,
+    pub amount: f64,
+    pub occurred_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Anomaly {
+    pub transaction: ClassifiedTransaction,
+    pub kinds: Vec<AnomalyKind>,
+}
+
+impl AnomalyHistoryRow {
+    /// When the purchase happened, in feed-local time. Only card events carry
+    /// a time; synced transactions have just a date.
+    fn occurred_at(&self) -> Option<NaiveDateTime> {
+        let offset = FixedOffset::east_opt(FEED_UTC_OFFSET_SECS)?;
+        self.occurred_at
+            .map(|at| at.with_timezone(&offset).naive_local())
+    }
+}
+
+/// Scores `transaction` (already present in `history`) against rows inserted
+/// before it.
+pub fn score_transaction(
+    transaction: &ClassifiedTransaction,
+    history: &[AnomalyHistoryRow],
+) -> Vec<AnomalyKind> {
+    let mut kinds = Vec::new();
+    let Some(current) = history.iter().find(|row| row.id == transaction.id) else {
+        return kinds;
+    };
+
+    let merchant = normalize_merchant(&current.description);
+    let amount = current.amount.abs();
+
+    let prior: Vec<&AnomalyHistoryRow> = history
+        .iter()
+        .filter(|row| row.id < current.id && row.account_id == current.account_id)
+        .collect();
+    let merchant_prior: Vec<&AnomalyHistoryRow> = prior
+        .iter()
+        .copied()
+        .filter(|row| normalize_merchant(&row.description) == merchant)
+        .collect();
+
+    let merchant_amounts: Vec<f64> = merchant_prior.iter().map(|row| row.amount.abs()).collect();
+    let bucket_amounts: Vec<f64> = prior
+        .iter()
+        .filter(|row| row.bucket.is_some() && row.bucket == current.bucket)
+        .map(|row| row.amount.abs())
+        .collect();
+
+    let unusual_amount = if merchant_amounts.len() >= MIN_MERCHANT_SAMPLES {
+        z_score(amount, &merchant_amounts) > Z_SCORE_THRESHOLD
+    } else if bucket_amounts.len() >= MIN_BUCKET_SAMPLES {
+        z_score(amount, &bucket_amounts) > Z_SCORE_THRESHOLD
+    } else {
+        false
+    };
+    if unusual_amount {
+        kinds.push(AnomalyKind::UnusualAmount);
+    }
+
+    if prior.len() >= MIN_ACCOUNT_HISTORY && merchant_prior.is_empty() {
+        kinds.push(AnomalyKind::NewMerchant);
+    }
+
+    if looks_foreign(&current.description) {
+        kinds.push(AnomalyKind::ForeignDescription);
+    }
+
+    // Date-only rows can't be placed within minutes of each other or in an
+    // hour, so only card events (and the rows they posted as) are checked.
+    if let Some(at) = current.occurred_at() {
+        let duplicate = merchant_prior.iter().any(|row| {
+            (row.amount.abs() - amount).abs() < 0.005
+                && row.occurred_at().is_some_and(|other| {
+                    (at - other).num_minutes().abs() <= DUPLICATE_WINDOW_MINUTES
+                })
+        });
+        if duplicate {
+            kinds.push(AnomalyKind::DuplicateCharge);
+        }
+
+        if UNUSUAL_HOURS.contains(&at.hour()) {
+            let timed_hours: Vec<u32> = prior
+                .iter()
+                .filter_map(|row| row.occurred_at())
+                .map(|at| at.hour())
+                .collect();
+            let unusual_share = if timed_hours.is_empty() {
+                0.0
+            } else {
+                timed_hours
+                    .iter()
+                    .filter(|hour| UNUSUAL_HOURS.contains(hour))
+                    .count() as f64
+                    / timed_hours.len() as f64
+            };
+            if unusual_share < UNUSUAL_HOUR_RATIO {
+                kinds.push(AnomalyKind::UnusualHour);
+            }
+        }
+    }
+
+    kinds
+}
+
+/// Heuristic for card descriptions from foreign merchants: non-ASCII text, a
+/// foreign currency code, or a trailing foreign country code.
+pub fn looks_foreign(description: &str) -> bool {
+    if !description.is_ascii() {
+        return true;
+    }
+
+    let tokens: Vec<String> = description
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect();
+
+    tokens
+        .iter()
+        .any(|token| FOREIGN_CURRENCIES.contains(&token.as_str()))
+        || tokens
+            .last()
+            .is_some_and(|token| FOREIGN_COUNTRY_SUFFIXES.contains(&token.as_str()))
+}
+
+fn z_score(value: f64, samples: &[f64]) -> f64 {
+    let count = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / count;
+    let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / count;
+    // Floor the deviation so perfectly constant histories don't flag cents
+    let std_dev = variance.sqrt().max(mean.abs() * 0.05).max(0.01);
+    (value - mean).abs() / std_dev
+}
+
+/// Scores newly inserted transactions, stores flags on their annotations and
+/// returns the transactions that were flagged.
+pub async fn evaluate_new_transactions(
+    pool: &PgPool,
+    transactions: &[ClassifiedTransaction],
+    today: NaiveDate,
+) -> Result<Vec<Anomaly>> {
+    if transactions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let since = today - Days::new(HISTORY_DAYS);
+    let history = db::fetch_anomaly_history(pool, since).await?;
+    let mut anomalies = Vec::new();
+
+    for transaction in transactions {
+        let kinds = score_transaction(transaction, &history);
+        if kinds.is_empty() {
+            continue;
+        }
+
+        let flags = anomaly_flags(&kinds);
+        db::set_annotation_anomalies(pool, transaction.id, &flags).await?;
+
+        anomalies.push(Anomaly {
+            transaction: transaction.clone(),
+            kinds,
+        });
+    }
+
+    Ok(anomalies)
+}
+
+/// Distinct flag names in declaration order, so stored annotations are stable.
+fn anomaly_flags(kinds: &[AnomalyKind]) -> Vec<&'static str> {
+    kinds
+        .iter()
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|kind| kind.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history_row(id: i32, description: &str, amount: f64) -> AnomalyHistoryRow {
+        AnomalyHistoryRow {
+            id,
+            account_id: "acc".to_string(),
+            description: description.to_string(),
+            bucket: Some("Food".to_string()),
+            amount,
+            occurred_at: None,
+        }
+    }
+
+    fn card_event_row(id: i32, description: &str, at: &str, amount: f64) -> AnomalyHistoryRow {
+        let occurred_at = DateTime::parse_from_rfc3339(at)
+            .unwrap()
+            .with_timezone(&Utc);
+        AnomalyHistoryRow {
+            occurred_at: Some(occurred_at),
+            ..history_row(id, description, amount)
+        }
+    }
+
+    fn classified(id: i32) -> ClassifiedTransaction {
+        ClassifiedTransaction {
+            id,
+            uuid: None,
+            account_id: "acc".to_string(),
+            description: String::new(),
+            amount: 0.0,
+            bucket: "Food".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_unusual_amount_against_merchant_history() {
+        let mut history: Vec<AnomalyHistoryRow> = (1..=6)
+            .map(|id| history_row(id, "WOOLWORTHS", 300.0 + id as f64))
+            .collect();
+        history.push(history_row(7, "WOOLWORTHS", 4000.0));
+
+        assert_eq!(
+            score_transaction(&classified(7), &history),
+            vec![AnomalyKind::UnusualAmount]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_charge_within_window() {
+        let history = vec![
+            card_event_row(1, "UBER TRIP", "2024-01-01T10:00:00Z", 85.0),
+            card_event_row(2, "UBER TRIP", "2024-01-01T10:04:00Z", 85.0),
+            card_event_row(3, "UBER TRIP", "2024-01-01T12:00:00Z", 85.0),
+        ];
+
+        assert_eq!(
+            score_transaction(&classified(2), &history),
+            vec![AnomalyKind::DuplicateCharge]
+        );
+        assert!(score_transaction(&classified(3), &history).is_empty());
+    }
+
+    #[test]
+    fn test_time_checks_skip_date_only_rows() {
+        let history = vec![
+            history_row(1, "UBER TRIP", 85.0),
+            history_row(2, "UBER TRIP", 85.0),
+        ];
+
+        assert!(score_transaction(&classified(2), &history).is_empty());
+    }
+
+    #[test]
+    fn test_unusual_hour_in_feed_local_time() {
+        let history = vec![
+            card_event_row(1, "SPAR", "2024-01-01T08:00:00Z", 85.0),
+            // 01:30 in South Africa
+            card_event_row(2, "KFC", "2024-01-01T23:30:00Z", 120.0),
+            card_event_row(3, "KFC", "2024-01-02T04:30:00Z", 95.0),
+        ];
+
+        assert_eq!(
+            score_transaction(&classified(2), &history),
+            vec![AnomalyKind::UnusualHour]
+        );
+        assert!(score_transaction(&classified(3), &history).is_empty());
+    }
+
+    #[test]
+    fn test_looks_foreign() {
+        assert!(looks_foreign("AMAZON WEB SERVICES USD 12.00"));
+        assert!(looks_foreign("STEAMGAMES.COM 4259522985 US"));
+        assert!(!looks_foreign("WOOLWORTHS CAPE TOWN"));
+    }
+
+    #[test]
+    fn test_anomaly_flags_are_distinct_and_ordered() {
+        let kinds = [
+            AnomalyKind::UnusualHour,
+            AnomalyKind::UnusualAmount,
+            AnomalyKind::NewMerchant,
+            AnomalyKind::UnusualAmount,
+        ];
+
+        assert_eq!(
+            anomaly_flags(&kinds),
+            vec!["unusual_amount", "new_merchant", "unusual_hour"]
+        );
+    }
+}
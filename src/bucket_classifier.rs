@@ -1,8 +1,9 @@
 use crate::clients::{GeminiClient, GoogleSearchClient, OllamaClient};
-use crate::config::settings::Config;
+use crate::config::settings::{BUCKET_OTHER, Config};
+use crate::monitoring;
 use anyhow::Result;
-
-const BUCKET_OTHER: &str = "Other";
+use std::time::Instant;
+use tracing::Instrument;
 
 #[derive(Debug)]
 pub struct BucketClassifier {
@@ -96,28 +97,37 @@ impl BucketClassifier {
         Ok(BUCKET_OTHER.to_string())
     }
 
+    #[tracing::instrument(
+        skip_all,
+        fields(uuid = ?transaction.uuid, account_id = %transaction.account_id)
+    )]
     pub async fn classify_transaction_with_fallback(
         &self,
         transaction: &crate::clients::investec::models::Transaction,
     ) -> Result<String> {
         if self.gemini_client.is_some() {
-            if let Ok(result) = self.try_gemini_with_search(transaction).await {
+            if let Ok(result) =
+                timed("gemini_search", self.try_gemini_with_search(transaction)).await
+            {
                 return Ok(result);
             }
         }
 
         if self.ollama_client.is_some() && self.search_client.is_some() {
-            if let Ok(result) = self.try_ollama_with_search(transaction).await {
+            if let Ok(result) =
+                timed("ollama_search", self.try_ollama_with_search(transaction)).await
+            {
                 return Ok(result);
             }
         }
 
         if self.ollama_client.is_some() {
-            if let Ok(result) = self.try_ollama_only(transaction).await {
+            if let Ok(result) = timed("ollama", self.try_ollama_only(transaction)).await {
                 return Ok(result);
             }
         }
 
+        monitoring::classifier_fallback();
         Ok(BUCKET_OTHER.to_string())
     }
 
@@ -224,6 +234,33 @@ impl BucketClassifier {
         }
     }
 
+    /// Pings every configured provider, e.g. for readiness checks.
+    pub async fn check_providers(&self) -> Vec<(&'static str, Result<()>)> {
+        let (gemini, ollama) = tokio::join!(
+            async {
+                match &self.gemini_client {
+                    Some(gemini) => Some(gemini.ping().await),
+                    None => None,
+                }
+            },
+            async {
+                match &self.ollama_client {
+                    Some(ollama) => Some(ollama.ping().await),
+                    None => None,
+                }
+            },
+        );
+
+        let mut checks = Vec::new();
+        if let Some(result) = gemini {
+            checks.push(("gemini", result));
+        }
+        if let Some(result) = ollama {
+            checks.push(("ollama", result));
+        }
+        checks
+    }
+
     fn process_classification_response(
         &self,
         response: &str,
@@ -242,51 +279,25 @@ impl BucketClassifier {
     }
 }
 
+/// Runs one classification strategy, recording its latency and outcome.
+async fn timed(
+    strategy: &'static str,
+    attempt: impl Future<Output = Result<String>>,
+) -> Result<String> {
+    let started = Instant::now();
+    let result = attempt
+        .instrument(tracing::info_span!("classify_attempt", strategy))
+        .await;
+    monitoring::classifier_attempt(strategy, result.is_ok(), started.elapsed());
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::settings::{
-        BucketsConfig, Config, DatabaseConfig, GeminiConfig, GoogleSearchConfig, InvestecConfig,
-        OllamaConfig,
-    };
 
     fn create_test_config() -> Config {
-        Config {
-            investec: InvestecConfig {
-                x_api_key: "test".to_string(),
-                client_id: "test".to_string(),
-                client_secret: "test".to_string(),
-            },
-            google_search: GoogleSearchConfig {
-                api_key: Some("test".to_string()),
-                engine_id: Some("test".to_string()),
-            },
-            gemini: GeminiConfig {
-                api_key: Some("test".to_string()),
-                model: Some("test".to_string()),
-            },
-            ollama: OllamaConfig {
-                model: Some("test".to_string()),
-                host: None,
-                port: None,
-            },
-            city: Some("cape town".to_string()),
-            database: DatabaseConfig {
-                url: "sqlite://test.db".to_string(),
-            },
-            buckets: BucketsConfig {
-                categories: vec![
-                    "Food".to_string(),
-                    "Transportation".to_string(),
-                    "Entertainment".to_string(),
-                    "Bills & Utilities".to_string(),
-                    "Healthcare".to_string(),
-                    "Income".to_string(),
-                    "Transfers".to_string(),
-                    "Other".to_string(),
-                ],
-            },
-        }
+        Config::for_tests()
     }
 
     #[test]
@@ -325,4 +336,16 @@ mod tests {
             "Other"
         );
     }
+
+    #[test]
+    fn test_debug_omits_api_keys() {
+        let mut config = create_test_config();
+        config.gemini.api_key = Some("gemini-key-123".into());
+        config.google_search.api_key = Some("search-key-456".into());
+        let classifier = BucketClassifier::new(Some("test".to_string()), &config);
+
+        let debug = format!("{:?}", classifier);
+        assert!(!debug.contains("gemini-key-123"));
+        assert!(!debug.contains("search-key-456"));
+    }
 }
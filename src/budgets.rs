@@ -0,0 +1,188 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::{Datelike, Days, Months, NaiveDate};
+use sqlx::PgPool;
+
+use crate::db;
+
+/// Percentages of a budget at which an alert is raised, once per period.
+pub const ALERT_THRESHOLDS: [i32; 3] = [50, 80, 100];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BudgetPeriod {
+    Weekly,
+    Monthly,
+}
+
+impl BudgetPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Weekly => "weekly",
+            BudgetPeriod::Monthly => "monthly",
+        }
+    }
+
+    /// Returns the first day of the period containing `date` and the first day
+    /// of the following period. Weeks start on Monday.
+    pub fn bounds(&self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            BudgetPeriod::Weekly => {
+                let start = date - Days::new(date.weekday().num_days_from_monday() as u64);
+                (start, start + Days::new(7))
+            }
+            BudgetPeriod::Monthly => {
+                let start = date.with_day(1).expect("first day of month is valid");
+                (start, start + Months::new(1))
+            }
+        }
+    }
+}
+
+impl fmt::Display for BudgetPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BudgetPeriod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "weekly" => Ok(BudgetPeriod::Weekly),
+            "monthly" => Ok(BudgetPeriod::Monthly),
+            other => Err(anyhow::anyhow!("Unknown budget period: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Budget {
+    pub id: i32,
+    pub bucket: String,
+    pub period: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BudgetStatus {
+    pub budget: Budget,
+    pub period_start: NaiveDate,
+    pub spent: f64,
+}
+
+impl BudgetStatus {
+    pub fn percent_used(&self) -> f64 {
+        self.spent / self.budget.amount * 100.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BudgetAlert {
+    pub bucket: String,
+    pub period: String,
+    pub period_start: NaiveDate,
+    pub threshold: i32,
+    pub spent: f64,
+    pub limit: f64,
+}
+
+/// Thresholds in [`ALERT_THRESHOLDS`] that `spent` has reached for `limit`.
+pub fn crossed_thresholds(spent: f64, limit: f64) -> Vec<i32> {
+    if limit <= 0.0 {
+        return Vec::new();
+    }
+
+    let percent = spent / limit * 100.0;
+    ALERT_THRESHOLDS
+        .iter()
+        .copied()
+        .filter(|threshold| percent >= *threshold as f64)
+        .collect()
+}
+
+pub async fn budget_statuses(pool: &PgPool, today: NaiveDate) -> Result<Vec<BudgetStatus>> {
+    let mut statuses = Vec::new();
+
+    for budget in db::list_budgets(pool).await? {
+        let period = BudgetPeriod::from_str(&budget.period)?;
+        let (period_start, period_end) = period.bounds(today);
+        let spent =
+            db::bucket_spend_between(pool, &budget.bucket, period_start, period_end).await?;
+
+        statuses.push(BudgetStatus {
+            budget,
+            period_start,
+            spent,
+        });
+    }
+
+    Ok(statuses)
+}
+
+/// Computes spend-to-date for every budget and records any newly crossed
+/// thresholds. Thresholds already alerted in the current period are skipped.
+pub async fn evaluate_budgets(pool: &PgPool, today: NaiveDate) -> Result<Vec<BudgetAlert>> {
+    let mut alerts = Vec::new();
+
+    for status in budget_statuses(pool, today).await? {
+        for threshold in crossed_thresholds(status.spent, status.budget.amount) {
+            let is_new = db::insert_budget_alert(
+                pool,
+                status.budget.id,
+                status.period_start,
+                threshold,
+                status.spent,
+            )
+            .await?;
+
+            if is_new {
+                alerts.push(BudgetAlert {
+                    bucket: status.budget.bucket.clone(),
+                    period: status.budget.period.clone(),
+                    period_start: status.period_start,
+                    threshold,
+                    spent: status.spent,
+                    limit: status.budget.amount,
+                });
+            }
+        }
+    }
+
+    Ok(alerts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossed_thresholds() {
+        assert!(crossed_thresholds(100.0, 1000.0).is_empty());
+        assert_eq!(crossed_thresholds(500.0, 1000.0), vec![50]);
+        assert_eq!(crossed_thresholds(850.0, 1000.0), vec![50, 80]);
+        assert_eq!(crossed_thresholds(1200.0, 1000.0), vec![50, 80, 100]);
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        let date = NaiveDate::from_ymd_opt(2024, 2, 15).unwrap();
+
+        assert_eq!(
+            BudgetPeriod::Monthly.bounds(date),
+            (
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()
+            )
+        );
+        assert_eq!(
+            BudgetPeriod::Weekly.bounds(date),
+            (
+                NaiveDate::from_ymd_opt(2024, 2, 12).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 2, 19).unwrap()
+            )
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Generates the `main.js` that runs on Investec programmable cards.
+//!
+//! Card rules tie merchant categories to a bucket. The generated
+//! `beforeTransaction` declines a purchase at one of those categories once it
+//! would take the bucket past its budget. Budgets are evaluated when the code
+//! is generated, so the code has to be redeployed to pick up new spend.
+//! With `CARD_WEBHOOK_URL` set, `afterTransaction` forwards every purchase to
+//! the card event webhook. The webhook secret is read from the card's
+//! environment variables so it never appears in the code.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use similar::TextDiff;
+use sqlx::PgPool;
+
+use crate::budgets;
+use crate::config::settings::CardWebhookConfig;
+use crate::db;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CardRule {
+    pub id: i32,
+    pub bucket: String,
+    pub merchant_category_code: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CardCodeDeployment {
+    pub version: i32,
+    pub code_id: String,
+    pub code: String,
+    pub published: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What is left to spend in a bucket at the merchant categories tied to it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CardLimit {
+    pub bucket: String,
+    pub categories: Vec<String>,
+    #[serde(rename = "remainingCents")]
+    pub remaining_cents: i64,
+}
+
+/// Card environment variable holding the secret sent with forwarded events.
+pub const WEBHOOK_SECRET_VARIABLE: &str = "CARD_WEBHOOK_SECRET";
+
+/// Where the generated `afterTransaction` posts each transaction.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventForwarding {
+    pub url: String,
+}
+
+impl EventForwarding {
+    pub fn from_config(config: &CardWebhookConfig) -> Option<Self> {
+        Some(Self {
+            url: config.url.clone()?,
+        })
+    }
+}
+
+/// Merchant category codes are four digits, e.g. `5812` for restaurants.
+pub fn is_merchant_category_code(code: &str) -> bool {
+    code.len() == 4 && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// One limit per bucket with rules. A bucket with several budgets is held to
+/// the one with the least left; rules on buckets without a budget are skipped.
+pub async fn card_limits(pool: &PgPool, today: NaiveDate) -> Result<Vec<CardLimit>> {
+    let statuses = budgets::budget_statuses(pool, today).await?;
+    let mut categories: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for rule in db::list_card_rules(pool).await? {
+        categories
+            .entry(rule.bucket)
+            .or_default()
+            .push(rule.merchant_category_code);
+    }
+
+    let mut limits = Vec::new();
+    for (bucket, categories) in categories {
+        let remaining = statuses
+            .iter()
+            .filter(|status| status.budget.bucket == bucket)
+            .map(|status| (status.budget.amount - status.spent).max(0.0))
+            .reduce(f64::min);
+        let Some(remaining) = remaining else {
+            tracing::warn!(bucket = %bucket, "Card rule bucket has no budget, skipping");
+            continue;
+        };
+
+        limits.push(CardLimit {
+            bucket,
+            categories,
+            remaining_cents: (remaining * 100.0).floor() as i64,
+        });
+    }
+
+    Ok(limits)
+}
+
+/// Renders `main.js` for `limits`. The output only depends on its input so
+/// regenerating unchanged budgets produces an empty diff.
+pub fn render(limits: &[CardLimit], forwarding: Option<&EventForwarding>) -> String {
+    let limits = serde_json::to_string_pretty(limits).expect("card limits serialize");
+    let after_transaction = match forwarding {
+        Some(forwarding) => format!(
+            r#"const webhook = {webhook};
+
+const afterTransaction = async (transaction) => {{
+  const response = await fetch(webhook.url, {{
+    method: "POST",
+    headers: {{
+      "Content-Type": "application/json",
+      "X-Webhook-Secret": process.env.{WEBHOOK_SECRET_VARIABLE},
+    }},
+    body: JSON.stringify(transaction),
+  }});
+  console.log(`Forwarded to the card event webhook: ${{response.status}}`);
+}};
+"#,
+            webhook = serde_json::to_string_pretty(forwarding).expect("webhook serializes")
+        ),
+        None => "const afterTransaction = async (transaction) => {};\n".to_string(),
+    };
+
+    format!(
+        r#"// Generated by investec-transaction-buckets from bucket budgets and card rules.
+// Edits made here are overwritten by the next `card deploy`.
+
+const limits = {limits};
+
+const beforeTransaction = async (authorization) => {{
+  const category = authorization.merchant.category.code;
+  for (const limit of limits) {{
+    if (limit.categories.includes(category) && authorization.centsAmount > limit.remainingCents) {{
+      console.log(`Declined: ${{limit.bucket}} has ${{limit.remainingCents}} cents left`);
+      return false;
+    }}
+  }}
+  return true;
+}};
+
+{after_transaction}"#
+    )
+}
+
+pub async fn generate(
+    pool: &PgPool,
+    today: NaiveDate,
+    forwarding: Option<&EventForwarding>,
+) -> Result<String> {
+    Ok(render(&card_limits(pool, today).await?, forwarding))
+}
+
+/// Unified diff from `old` to `new`, empty when they match.
+pub fn diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(old_name, new_name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_embeds_limits_deterministically() {
+        let limits = vec![CardLimit {
+            bucket: "Entertainment \"fun\"".to_string(),
+            categories: vec!["7832".to_string(), "7841".to_string()],
+            remaining_cents: 0,
+        }];
+
+        let code = render(&limits, None);
+        assert!(code.contains(r#""bucket": "Entertainment \"fun\"""#));
+        assert!(code.contains(r#""remainingCents": 0"#));
+        assert!(code.contains("const beforeTransaction = async (authorization) => {"));
+        assert_eq!(code, render(&limits, None));
+        assert!(render(&[], None).contains("const limits = [];"));
+    }
+
+    #[test]
+    fn test_render_forwards_card_events() {
+        assert!(render(&[], None).contains("const afterTransaction = async (transaction) => {};"));
+
+        let forwarding = EventForwarding {
+            url: "https://example.com/card-events".to_string(),
+        };
+        let code = render(&[], Some(&forwarding));
+        assert!(code.contains(r#""url": "https://example.com/card-events""#));
+        assert!(!code.contains(r#""secret""#));
+        assert!(code.contains("const response = await fetch(webhook.url, {"));
+        assert!(code.contains(r#""X-Webhook-Secret": process.env.CARD_WEBHOOK_SECRET,"#));
+        assert!(code.contains("body: JSON.stringify(transaction),"));
+    }
+
+    #[test]
+    fn test_diff_and_category_codes() {
+        assert_eq!(diff("a\n", "a\n", "v1", "generated"), "");
+        let changed = diff("a\nb\n", "a\nc\n", "v1", "generated");
+        assert!(changed.starts_with("--- v1\n+++ generated\n"));
+        assert!(changed.contains("-b\n+c\n"));
+
+        assert!(is_merchant_category_code("5812"));
+        assert!(!is_merchant_category_code("581"));
+        assert!(!is_merchant_category_code("58a2"));
+    }
+}
@@ -0,0 +1,466 @@
+//! Receiver for card events forwarded by Programmable Banking card code.
+//!
+//! Card code running on the card POSTs the `afterTransaction` transaction
+//! object here. The purchase is classified straight away and stored as a
+//! pending transaction and scored for anomalies; the hourly sync later
+//! matches it to the posted transaction instead of inserting a second row.
+//! Events that never post are dropped after a while.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::PgPool;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::bucket_classifier::BucketClassifier;
+use crate::clients::InvestecClient;
+use crate::clients::investec::models;
+use crate::config::secret::Secret;
+use crate::db;
+use crate::monitoring;
+use crate::notifications::{ClassifiedTransaction, Event, Notifier};
+use crate::scheduler;
+use crate::shutdown::Shutdown;
+
+/// Hex HMAC-SHA256 of the raw body, optionally prefixed with `sha256=`.
+pub const SIGNATURE_HEADER: &str = "x-signature";
+/// The shared secret itself, for card code that can't compute an HMAC.
+pub const SECRET_HEADER: &str = "x-webhook-secret";
+
+/// Shortest time between account refreshes triggered by unknown account
+/// numbers, so repeated unknown numbers don't turn into Investec calls.
+const ACCOUNT_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The parts of the `afterTransaction` transaction object that get stored.
+#[derive(Debug, Deserialize)]
+pub struct CardEvent {
+    #[serde(rename = "accountNumber")]
+    pub account_number: String,
+    #[serde(rename = "dateTime")]
+    pub date_time: String,
+    #[serde(rename = "centsAmount")]
+    pub cents_amount: i64,
+    pub reference: Option<String>,
+    pub card: Card,
+    pub merchant: Merchant,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Card {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Merchant {
+    pub name: String,
+}
+
+impl CardEvent {
+    /// Identifies the event so a redelivered event isn't stored twice.
+    pub fn event_id(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.card.id,
+            self.date_time,
+            self.cents_amount,
+            self.reference.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// When the purchase happened.
+    pub fn occurred_at(&self) -> Result<DateTime<Utc>> {
+        Ok(DateTime::parse_from_rfc3339(&self.date_time)
+            .map_err(|e| anyhow::anyhow!("Invalid dateTime {:?}: {}", self.date_time, e))?
+            .with_timezone(&Utc))
+    }
+
+    /// The pending card purchase as the sync would have seen it.
+    pub fn to_transaction(&self, account_id: &str) -> Result<models::Transaction> {
+        let date = DateTime::parse_from_rfc3339(&self.date_time)
+            .map_err(|e| anyhow::anyhow!("Invalid dateTime {:?}: {}", self.date_time, e))?
+            .date_naive()
+            .format("%Y-%m-%d")
+            .to_string();
+
+        Ok(models::Transaction {
+            account_id: account_id.to_string(),
+            type_: "DEBIT".to_string(),
+            transaction_type: Some("CardPurchases".to_string()),
+            status: "PENDING".to_string(),
+            description: self.merchant.name.trim().to_string(),
+            card_number: None,
+            posted_order: None,
+            posting_date: None,
+            value_date: None,
+            action_date: None,
+            transaction_date: Some(date),
+            amount: self.cents_amount.abs() as f64 / 100.0,
+            running_balance: None,
+            uuid: None,
+        })
+    }
+}
+
+/// Checks the request against the shared secret, accepting either an HMAC
+/// signature of the body or the secret itself.
+pub fn verify(secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+    if let Some(signature) = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        let signature = signature.trim();
+        let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        let Ok(mut mac) = HmacSha256::new_from_slice(secret.as_bytes()) else {
+            return false;
+        };
+        mac.update(body);
+        return mac.verify_slice(&signature).is_ok();
+    }
+
+    match headers
+        .get(SECRET_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(provided) => constant_time_eq(provided.as_bytes(), secret.as_bytes()),
+        None => false,
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Default)]
+struct AccountCache {
+    /// Account numbers to profiles and account ids.
+    accounts: HashMap<String, (String, String)>,
+    refreshed_at: Option<Instant>,
+}
+
+pub struct WebhookState {
+    secret: Secret,
+    clients: Arc<Vec<InvestecClient>>,
+    classifier: Arc<BucketClassifier>,
+    notifier: Arc<Notifier>,
+    pool: PgPool,
+    /// Refreshed when an unknown number arrives, at most once per
+    /// `ACCOUNT_REFRESH_INTERVAL`.
+    accounts: std::sync::Mutex<AccountCache>,
+    /// Held while refreshing so concurrent misses share one refresh.
+    refresh: Mutex<()>,
+}
+
+impl WebhookState {
+    pub fn new(
+        secret: Secret,
+        clients: Arc<Vec<InvestecClient>>,
+        classifier: Arc<BucketClassifier>,
+        notifier: Arc<Notifier>,
+        pool: PgPool,
+    ) -> Self {
+        Self {
+            secret,
+            clients,
+            classifier,
+            notifier,
+            pool,
+            accounts: std::sync::Mutex::new(AccountCache::default()),
+            refresh: Mutex::new(()),
+        }
+    }
+
+    /// The profile and account id of an account number, looked up across
+    /// every profile. Known numbers never wait on Investec.
+    async fn account(&self, account_number: &str) -> Result<Option<(String, String)>> {
+        if let Some(account) = self.cached_account(account_number) {
+            return Ok(Some(account));
+        }
+
+        let _refresh = self.refresh.lock().await;
+        {
+            let mut cache = self.accounts.lock().unwrap();
+            if let Some(account) = cache.accounts.get(account_number) {
+                return Ok(Some(account.clone()));
+            }
+            if cache
+                .refreshed_at
+                .is_some_and(|at| at.elapsed() < ACCOUNT_REFRESH_INTERVAL)
+            {
+                return Ok(None);
+            }
+            // Failed refreshes count too, so an outage isn't retried per request.
+            cache.refreshed_at = Some(Instant::now());
+        }
+
+        let mut refreshed = HashMap::new();
+        for client in self.clients.iter() {
+            for account in client.get_accounts().await? {
+                refreshed.insert(
+                    account.account_number,
+                    (client.profile().to_string(), account.account_id),
+                );
+            }
+        }
+
+        let mut cache = self.accounts.lock().unwrap();
+        cache.accounts = refreshed;
+        Ok(cache.accounts.get(account_number).cloned())
+    }
+
+    fn cached_account(&self, account_number: &str) -> Option<(String, String)> {
+        self.accounts
+            .lock()
+            .unwrap()
+            .accounts
+            .get(account_number)
+            .cloned()
+    }
+}
+
+pub fn router(state: Arc<WebhookState>) -> Router {
+    Router::new()
+        .route("/card-events", post(card_event))
+        .with_state(state)
+}
+
+pub async fn spawn(
+    addr: &str,
+    state: Arc<WebhookState>,
+    shutdown: Arc<Shutdown>,
+) -> Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+
+    let handle = tokio::spawn(async move {
+        // Stops accepting connections on shutdown and finishes open requests.
+        let server = axum::serve(listener, router(state))
+            .with_graceful_shutdown(async move { shutdown.requested().await });
+        if let Err(e) = server.await {
+            tracing::error!(error = %e, "Card webhook server stopped");
+        }
+    });
+
+    Ok((local_addr, handle))
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+async fn card_event(
+    State(state): State<Arc<WebhookState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !verify(state.secret.expose(), &headers, &body) {
+        return error(StatusCode::UNAUTHORIZED, "invalid signature");
+    }
+
+    let event: CardEvent = match serde_json::from_slice(&body) {
+        Ok(event) => event,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+
+    let (profile, account_id) = match state.account(&event.account_number).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return error(StatusCode::NOT_FOUND, "unknown account"),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to resolve card event account");
+            return error(StatusCode::BAD_GATEWAY, "failed to resolve account");
+        }
+    };
+
+    let transaction = match event.to_transaction(&account_id) {
+        Ok(transaction) => transaction,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+
+    let event_id = event.event_id();
+    match db::find_transaction_id_by_card_event(&state.pool, &event_id).await {
+        Ok(Some(id)) => return (StatusCode::OK, Json(json!({ "id": id }))).into_response(),
+        Ok(None) => {}
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to look up card event");
+            return error(StatusCode::INTERNAL_SERVER_ERROR, "database error");
+        }
+    }
+
+    // Left for the hourly sync to pick up once posted.
+    let bucket = match state
+        .classifier
+        .classify_transaction_with_fallback(&transaction)
+        .await
+    {
+        Ok(bucket) => bucket,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to classify card event");
+            return error(StatusCode::BAD_GATEWAY, "classification failed");
+        }
+    };
+
+    let id = match db::insert_card_event(
+        &state.pool,
+        &profile,
+        &event_id,
+        &transaction,
+        &bucket,
+        event.occurred_at().ok(),
+    )
+    .await
+    {
+        Ok(Some(id)) => {
+            monitoring::transaction_inserted("card_event");
+            id
+        }
+        Ok(None) => {
+            return (StatusCode::OK, Json(json!({ "status": "duplicate" }))).into_response();
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to store card event");
+            return error(StatusCode::INTERNAL_SERVER_ERROR, "database error");
+        }
+    };
+
+    tracing::info!(id, account_id = %account_id, bucket = %bucket, "Card event stored");
+    let stored = [ClassifiedTransaction {
+        id,
+        uuid: None,
+        account_id,
+        description: transaction.description.clone(),
+        amount: transaction.amount,
+        bucket: bucket.clone(),
+    }];
+    state
+        .notifier
+        .notify(Event::new_transactions(&stored))
+        .await;
+    // The sync reconciles the posted transaction into this row without
+    // scoring it again.
+    scheduler::check_anomalies(&state.pool, &state.notifier, &stored).await;
+
+    (
+        StatusCode::CREATED,
+        Json(json!({ "id": id, "bucket": bucket })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::settings::Config;
+    use crate::mock_investec::{self, MockState, fixtures};
+
+    const PAYLOAD: &str = r#"{
+        "accountNumber": "10012345678",
+        "dateTime": "2024-03-05T11:15:32.286Z",
+        "centsAmount": 12550,
+        "currencyCode": "zar",
+        "type": "card",
+        "reference": "simulation",
+        "card": { "id": "65321" },
+        "merchant": {
+            "category": { "code": "5462", "key": "bakeries", "name": "Bakeries" },
+            "name": "The Coders Bakery ",
+            "city": "Cape Town",
+            "country": { "code": "ZA", "alpha3": "ZAF", "name": "South Africa" }
+        }
+    }"#;
+
+    fn signature(secret: &str, body: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn test_verify_signature_or_secret() {
+        let body = PAYLOAD.as_bytes();
+
+        let mut headers = HeaderMap::new();
+        assert!(!verify("s3cret", &headers, body));
+
+        headers.insert(SIGNATURE_HEADER, signature("s3cret", body).parse().unwrap());
+        assert!(verify("s3cret", &headers, body));
+        assert!(!verify("other", &headers, body));
+        assert!(!verify("s3cret", &headers, b"{}"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(SECRET_HEADER, "s3cret".parse().unwrap());
+        assert!(verify("s3cret", &headers, body));
+        assert!(!verify("s3cret!", &headers, body));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_accounts_refresh_at_most_once_per_interval() {
+        let mock = Arc::new(MockState::new(fixtures::Fixtures::seed(
+            chrono::Utc::now().date_naive(),
+        )));
+        let (addr, _) = mock_investec::spawn("127.0.0.1:0", mock.clone())
+            .await
+            .unwrap();
+        let mut config = Config::for_tests();
+        config.investec.base_url = format!("http://{}", addr);
+        let state = WebhookState::new(
+            "s3cret".into(),
+            Arc::new(vec![InvestecClient::new(config.clone()).unwrap()]),
+            Arc::new(BucketClassifier::new(None, &config)),
+            Arc::new(Notifier::new(1, Duration::ZERO)),
+            PgPool::connect_lazy("postgres://localhost/unused").unwrap(),
+        );
+
+        assert_eq!(state.account("00000000000").await.unwrap(), None);
+
+        // Any further accounts call would now fail.
+        for _ in 0..3 {
+            mock.inject_failure(StatusCode::INTERNAL_SERVER_ERROR, None);
+        }
+        assert_eq!(state.account("00000000000").await.unwrap(), None);
+        assert_eq!(
+            state.account("10010206147").await.unwrap(),
+            Some((
+                "default".to_string(),
+                fixtures::CHEQUE_ACCOUNT_ID.to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_card_event_to_pending_transaction() {
+        let event: CardEvent = serde_json::from_str(PAYLOAD).unwrap();
+        let transaction = event.to_transaction("acc-1").unwrap();
+
+        assert_eq!(transaction.account_id, "acc-1");
+        assert_eq!(transaction.type_, "DEBIT");
+        assert_eq!(transaction.status, "PENDING");
+        assert_eq!(transaction.description, "The Coders Bakery");
+        assert_eq!(transaction.transaction_date.as_deref(), Some("2024-03-05"));
+        assert_eq!(transaction.amount, 125.5);
+        assert!(transaction.uuid.is_none());
+        assert_eq!(
+            event.event_id(),
+            "65321:2024-03-05T11:15:32.286Z:12550:simulation"
+        );
+    }
+}
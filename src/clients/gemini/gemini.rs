@@ -1,10 +1,18 @@
 use anyhow::Result;
 use gemini_rust::{Gemini, Tool};
 use std::fmt;
+use std::time::Duration;
+
+use crate::config::secret::Secret;
+
+const MODELS_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct GeminiClient {
     client: Gemini,
+    http: reqwest::Client,
     model: String,
+    api_key: Secret,
 }
 
 impl fmt::Debug for GeminiClient {
@@ -17,9 +25,18 @@ impl fmt::Debug for GeminiClient {
 }
 
 impl GeminiClient {
-    pub fn new(api_key: String, model: String) -> Self {
-        let client = Gemini::new(api_key).expect("unable to create Gemini API client");
-        Self { client, model }
+    pub fn new(api_key: Secret, model: String) -> Self {
+        let client = Gemini::new(api_key.expose()).expect("unable to create Gemini API client");
+        let http = reqwest::Client::builder()
+            .timeout(PING_TIMEOUT)
+            .build()
+            .expect("unable to create HTTP client");
+        Self {
+            client,
+            http,
+            model,
+            api_key,
+        }
     }
 
     pub async fn generate_text_with_search(&self, prompt: &str) -> Result<String> {
@@ -35,4 +52,21 @@ impl GeminiClient {
         let text = response.text();
         Ok(text)
     }
+
+    /// Checks that the API key is accepted and the model exists, without
+    /// generating anything.
+    pub async fn ping(&self) -> Result<()> {
+        let url = format!(
+            "{}/{}",
+            MODELS_URL,
+            self.model.trim_start_matches("models/")
+        );
+        self.http
+            .get(url)
+            .header("x-goog-api-key", self.api_key.expose())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
 }
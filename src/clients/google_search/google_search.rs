@@ -2,16 +2,17 @@ use anyhow::Result;
 use reqwest::Client;
 
 use super::models::GoogleSearchResponse;
+use crate::config::secret::Secret;
 
 #[derive(Debug)]
 pub struct GoogleSearchClient {
     client: Client,
-    api_key: String,
+    api_key: Secret,
     search_engine_id: String,
 }
 
 impl GoogleSearchClient {
-    pub fn new(api_key: String, search_engine_id: String) -> Self {
+    pub fn new(api_key: Secret, search_engine_id: String) -> Self {
         Self {
             client: Client::new(),
             api_key,
@@ -20,14 +21,22 @@ impl GoogleSearchClient {
     }
 
     pub async fn search(&self, query: &str) -> Result<String> {
-        let search_url = format!(
-            "https://www.googleapis.com/customsearch/v1?key={}&cx={}&q={}&num=3",
-            self.api_key,
-            self.search_engine_id,
-            urlencoding::encode(query)
-        );
-
-        let response_text = self.client.get(&search_url).send().await?.text().await?;
+        // The key travels in the query string, so keep the URL out of errors.
+        let response_text = self
+            .client
+            .get("https://www.googleapis.com/customsearch/v1")
+            .query(&[
+                ("key", self.api_key.expose()),
+                ("cx", &self.search_engine_id),
+                ("q", query),
+                ("num", "3"),
+            ])
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?
+            .text()
+            .await
+            .map_err(reqwest::Error::without_url)?;
 
         match serde_json::from_str::<GoogleSearchResponse>(&response_text) {
             Ok(response) => {
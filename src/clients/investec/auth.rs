@@ -1,41 +1,69 @@
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::config::secret::Secret;
 use crate::config::settings::Config;
+use crate::monitoring;
 use anyhow::Result;
 use reqwest::Client;
+use url::Url;
 
+use super::errors::InvestecError;
 use super::models::TokenResponse;
+use super::retry::is_retryable_status;
+
+const READ_ONLY_SCOPE: &str = "accounts";
+const PAYMENTS_SCOPE: &str = "transfers beneficiarypayments";
+const CARDS_SCOPE: &str = "cards";
+
+/// Read-only access plus whatever the config opted into.
+fn scope(payments_enabled: bool, cards_enabled: bool) -> String {
+    let mut scopes = vec![READ_ONLY_SCOPE];
+    if payments_enabled {
+        scopes.push(PAYMENTS_SCOPE);
+    }
+    if cards_enabled {
+        scopes.push(CARDS_SCOPE);
+    }
+    scopes.join(" ")
+}
 
 #[derive(Debug)]
 pub struct TokenState {
-    pub access_token: String,
+    pub access_token: Secret,
     pub expires_at: u64,
 }
 
 pub struct Authenticator {
     http: Client,
-    pub api_key: String,
+    token_url: Url,
+    pub api_key: Secret,
     investec_client_id: String,
-    investec_client_secret: String,
+    investec_client_secret: Secret,
+    scope: String,
     token: Arc<Mutex<TokenState>>,
 }
 
 impl Authenticator {
-    pub fn new(config: &Config) -> Self {
-        Self {
+    pub fn new(config: &Config, base: &Url) -> Result<Self> {
+        Ok(Self {
             http: Client::builder()
                 .timeout(std::time::Duration::from_secs(30))
                 .build()
                 .expect("Failed to build HTTP client"),
+            token_url: base.join("identity/v2/oauth2/token")?,
             api_key: config.investec.x_api_key.clone(),
             investec_client_id: config.investec.client_id.clone(),
             investec_client_secret: config.investec.client_secret.clone(),
+            scope: scope(
+                config.investec.payments_enabled,
+                config.investec.cards_enabled,
+            ),
             token: Arc::new(Mutex::new(TokenState {
-                access_token: String::new(),
+                access_token: Secret::default(),
                 expires_at: 0,
             })),
-        }
+        })
     }
 
     pub fn is_token_expired(&self) -> bool {
@@ -48,26 +76,43 @@ impl Authenticator {
         token_state.expires_at.saturating_sub(current_time) < five_minutes_buffer
     }
 
-    pub async fn authenticate(&self) -> Result<()> {
+    pub async fn authenticate(&self) -> Result<(), InvestecError> {
+        let result = self.request_token().await;
+        monitoring::investec_token_refresh(result.is_ok());
+        result
+    }
+
+    async fn request_token(&self) -> Result<(), InvestecError> {
         let response = self
             .http
-            .post("https://openapi.investec.com/identity/v2/oauth2/token")
-            .header("x-api-key", &self.api_key)
+            .post(self.token_url.clone())
+            .header("x-api-key", self.api_key.expose())
             .form(&[
                 ("grant_type", "client_credentials"),
                 ("client_id", &self.investec_client_id),
-                ("client_secret", &self.investec_client_secret),
-                ("scope", "accounts"),
+                ("client_secret", self.investec_client_secret.expose()),
+                ("scope", &self.scope),
             ])
             .send()
             .await?;
 
-        if !response.status().is_success() {
-            let body = response.text().await?;
-            return Err(anyhow::anyhow!("Authentication failed: {}", body));
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            // Outages on the token endpoint aren't credential problems.
+            if is_retryable_status(status) {
+                return Err(InvestecError::from_status(
+                    status,
+                    &body,
+                    None,
+                    "access token",
+                ));
+            }
+            return Err(InvestecError::auth(status, &body));
         }
 
-        let token_response: TokenResponse = response.json().await?;
+        let token_response: TokenResponse =
+            serde_json::from_str(&body).map_err(|e| InvestecError::decode(e, &body))?;
 
         let expires_at = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -76,18 +121,24 @@ impl Authenticator {
             + token_response.expires_in;
 
         let mut token_state = self.token.lock().unwrap();
-        token_state.access_token = token_response.access_token;
+        token_state.access_token = token_response.access_token.into();
         token_state.expires_at = expires_at;
 
         Ok(())
     }
 
-    pub async fn get_valid_token(&self) -> Result<String> {
+    /// Forces the next `get_valid_token` call to request a fresh token.
+    pub fn invalidate_token(&self) {
+        let mut token_state = self.token.lock().unwrap();
+        token_state.expires_at = 0;
+    }
+
+    pub async fn get_valid_token(&self) -> Result<String, InvestecError> {
         if self.is_token_expired() {
             self.authenticate().await?;
         }
 
         let token_state = self.token.lock().unwrap();
-        Ok(token_state.access_token.clone())
+        Ok(token_state.access_token.expose().to_string())
     }
 }
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use super::retry::CircuitOpenError;
+
+/// Longest slice of a response body kept in an error message.
+const BODY_EXCERPT_CHARS: usize = 200;
+
+#[derive(Debug, Error)]
+pub enum InvestecError {
+    #[error("Investec authentication failed with status {status}: {body}")]
+    Auth { status: StatusCode, body: String },
+
+    #[error("Investec rate limit exceeded{}", retry_after_suffix(.retry_after))]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error("Investec resource not found: {resource}")]
+    NotFound { resource: String },
+
+    #[error("Investec server error with status {status}: {body}")]
+    Server { status: StatusCode, body: String },
+
+    #[error("Investec rejected the request with status {status}: {body}")]
+    Request { status: StatusCode, body: String },
+
+    #[error("Investec rejected the transfer: {message}")]
+    PaymentRejected { message: String },
+
+    #[error("Transfers and payments are disabled, set INVESTEC_ENABLE_PAYMENTS=true to allow them")]
+    PaymentsDisabled,
+
+    #[error("Programmable card access is disabled, set INVESTEC_ENABLE_CARDS=true to allow it")]
+    CardsDisabled,
+
+    #[error("Failed to decode Investec response: {source} (body: {body})")]
+    Decode {
+        #[source]
+        source: serde_json::Error,
+        body: String,
+    },
+
+    #[error("Investec network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    CircuitOpen(#[from] CircuitOpenError),
+
+    #[error("Invalid Investec URL: {0}")]
+    Url(#[from] url::ParseError),
+}
+
+impl InvestecError {
+    /// Maps a non-success response to the matching variant. `resource`
+    /// describes what was requested and is only used for `NotFound`.
+    pub fn from_status(
+        status: StatusCode,
+        body: &str,
+        retry_after: Option<Duration>,
+        resource: &str,
+    ) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Self::auth(status, body),
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited { retry_after },
+            StatusCode::NOT_FOUND => Self::NotFound {
+                resource: resource.to_string(),
+            },
+            status if status.is_server_error() => Self::Server {
+                status,
+                body: excerpt(body),
+            },
+            status => Self::Request {
+                status,
+                body: excerpt(body),
+            },
+        }
+    }
+
+    pub fn auth(status: StatusCode, body: &str) -> Self {
+        Self::Auth {
+            status,
+            body: excerpt(body),
+        }
+    }
+
+    pub fn decode(source: serde_json::Error, body: &str) -> Self {
+        Self::Decode {
+            source,
+            body: excerpt(body),
+        }
+    }
+
+    /// Network failures worth retrying: timeouts and failed connections.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Network(e) if e.is_timeout() || e.is_connect() || e.is_request())
+    }
+
+    /// Whether a request that failed with this error may still have been
+    /// carried out by Investec, e.g. a transfer that timed out after being
+    /// sent. Such requests must not be repeated blindly.
+    pub fn outcome_unknown(&self) -> bool {
+        match self {
+            Self::Network(e) => !e.is_connect() && !e.is_builder(),
+            Self::Server { .. } | Self::Decode { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+fn excerpt(body: &str) -> String {
+    let body = body.trim();
+    match body.char_indices().nth(BODY_EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+fn retry_after_suffix(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(retry_after) => format!(", retry after {}s", retry_after.as_secs()),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_status_maps_variants() {
+        assert!(matches!(
+            InvestecError::from_status(StatusCode::UNAUTHORIZED, "", None, "accounts"),
+            InvestecError::Auth { .. }
+        ));
+        assert!(matches!(
+            InvestecError::from_status(StatusCode::TOO_MANY_REQUESTS, "", None, "accounts"),
+            InvestecError::RateLimited { .. }
+        ));
+        assert!(matches!(
+            InvestecError::from_status(StatusCode::NOT_FOUND, "", None, "account 1"),
+            InvestecError::NotFound { resource } if resource == "account 1"
+        ));
+        assert!(matches!(
+            InvestecError::from_status(StatusCode::BAD_GATEWAY, "", None, "accounts"),
+            InvestecError::Server { .. }
+        ));
+        assert!(matches!(
+            InvestecError::from_status(StatusCode::BAD_REQUEST, "", None, "accounts"),
+            InvestecError::Request { .. }
+        ));
+    }
+
+    #[test]
+    fn test_outcome_unknown() {
+        assert!(
+            InvestecError::from_status(StatusCode::GATEWAY_TIMEOUT, "", None, "account 1")
+                .outcome_unknown()
+        );
+        assert!(
+            !InvestecError::from_status(StatusCode::BAD_REQUEST, "", None, "account 1")
+                .outcome_unknown()
+        );
+        assert!(
+            !InvestecError::PaymentRejected {
+                message: "Insufficient funds".to_string()
+            }
+            .outcome_unknown()
+        );
+    }
+
+    #[test]
+    fn test_decode_error_keeps_body_excerpt() {
+        let body = format!("<html>{}</html>", "x".repeat(500));
+        let source = serde_json::from_str::<serde_json::Value>(&body).unwrap_err();
+
+        let message = InvestecError::decode(source, &body).to_string();
+
+        assert!(message.contains("<html>xxx"));
+        assert!(message.ends_with("…)"));
+        assert!(message.len() < 300);
+    }
+}
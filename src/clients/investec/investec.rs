@@ -1,91 +1,883 @@
+use std::time::{Duration, Instant};
+
 use crate::config::settings::Config;
+use crate::monitoring;
 use anyhow::Result;
-use reqwest::Client;
+use chrono::Utc;
+use reqwest::{Client, RequestBuilder, Response, StatusCode, header};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use url::Url;
 
 use super::auth::Authenticator;
-use super::models::{Account, AccountsResponse, ApiResponse, TransactionsResponse};
+use super::errors::InvestecError;
+use super::models::{
+    Account, AccountsResponse, ApiResponse, Balance, Beneficiary, BeneficiaryCategory, Card,
+    CardCode, CardEnvironment, CardResult, CardsResponse, CodeExecution, PaymentInstruction,
+    PaymentRequest, ProgrammableFeature, PublishCodeRequest, SaveCodeRequest, Simulation,
+    TransactionsResponse, TransferInstruction, TransferRequest, TransferResponse, TransferResult,
+};
+use super::query::TransactionQuery;
+use super::retry::{self, CircuitBreaker, RetryPolicy};
 
 const API_KEY_HEADER: &str = "x-api-key";
 
+/// Guards against a server that keeps reporting more pages.
+const MAX_TRANSACTION_PAGES: u32 = 500;
+
 pub struct InvestecClient {
+    profile: String,
     http: Client,
     base: Url,
     authenticator: Authenticator,
+    retry_policy: RetryPolicy,
+    circuit_breaker: CircuitBreaker,
+    payments_enabled: bool,
+    cards_enabled: bool,
 }
 
 impl InvestecClient {
     pub fn new(config: Config) -> Result<Self> {
+        let base = Url::parse(&with_trailing_slash(&config.investec.base_url))?;
+        let authenticator = Authenticator::new(&config, &base)?;
+
         Ok(Self {
+            profile: config.investec.profile.clone(),
             http: Client::builder()
                 .timeout(std::time::Duration::from_secs(30))
                 .build()?,
-            base: Url::parse("https://openapi.investec.com")?,
-            authenticator: Authenticator::new(&config),
+            base,
+            authenticator,
+            retry_policy: RetryPolicy::from_config(&config.investec),
+            circuit_breaker: CircuitBreaker::new(
+                config.investec.circuit_failure_threshold,
+                Duration::from_secs(config.investec.circuit_cooldown_secs),
+            ),
+            payments_enabled: config.investec.payments_enabled,
+            cards_enabled: config.investec.cards_enabled,
         })
     }
 
-    pub async fn get_accounts(&self) -> Result<Vec<Account>> {
-        let token = self.authenticator.get_valid_token().await?;
-        let url = self.base.join("za/pb/v1/accounts")?;
-
-        let response = self
-            .http
-            .get(url)
-            .header(API_KEY_HEADER, &self.authenticator.api_key)
-            .bearer_auth(token)
-            .send()
-            .await?;
+    /// One client per configured profile, each with its own token cache.
+    pub fn for_profiles(config: &Config) -> Result<Vec<Self>> {
+        config
+            .investec_profiles
+            .iter()
+            .map(|profile| {
+                let mut config = config.clone();
+                config.investec = profile.clone();
+                Self::new(config)
+            })
+            .collect()
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await?;
-            return Err(anyhow::anyhow!(
-                "API request failed with status {}: {}",
-                status,
-                body
-            ));
-        }
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
 
-        let body = response.text().await?;
+    /// Obtains an access token unless a valid one is cached.
+    pub async fn ensure_token(&self) -> Result<(), InvestecError> {
+        self.authenticator.get_valid_token().await.map(|_| ())
+    }
 
-        let api_response: ApiResponse<AccountsResponse> = serde_json::from_str(&body)?;
+    pub async fn get_accounts(&self) -> Result<Vec<Account>, InvestecError> {
+        let url = self.base.join("za/pb/v1/accounts")?;
+        let api_response: ApiResponse<AccountsResponse> =
+            self.get_json(url, &[], "accounts").await?;
         Ok(api_response.data.accounts)
     }
 
+    /// Fetches every page of transactions matching `query`.
     pub async fn get_transactions(
         &self,
         account_id: &str,
-        from_date: &str,
-        to_date: &str,
-    ) -> Result<TransactionsResponse> {
-        let token = self.authenticator.get_valid_token().await?;
+        query: &TransactionQuery,
+    ) -> Result<TransactionsResponse, InvestecError> {
         let url = self
             .base
             .join(&format!("za/pb/v1/accounts/{}/transactions", account_id))?;
+        let resource = format!("account {}", account_id);
+        let params = query.params();
+        let mut transactions = Vec::new();
+
+        for page in 1..=MAX_TRANSACTION_PAGES {
+            let page_param = page.to_string();
+            let mut query: Vec<(&str, &str)> = params
+                .iter()
+                .map(|(key, value)| (*key, value.as_str()))
+                .collect();
+            if page > 1 {
+                query.push(("page", page_param.as_str()));
+            }
+
+            let api_response: ApiResponse<TransactionsResponse> =
+                self.get_json(url.clone(), &query, &resource).await?;
+            let has_more = api_response.has_more_pages(page);
+            let page_was_empty = api_response.data.transactions.is_empty();
+            transactions.extend(api_response.data.transactions);
+
+            if !has_more || page_was_empty {
+                return Ok(TransactionsResponse { transactions });
+            }
+        }
+
+        tracing::warn!(
+            account_id,
+            pages = MAX_TRANSACTION_PAGES,
+            "Stopped following transaction pages, results are truncated"
+        );
+        Ok(TransactionsResponse { transactions })
+    }
+
+    /// Transactions that have been authorised but not yet posted.
+    pub async fn get_pending_transactions(
+        &self,
+        account_id: &str,
+    ) -> Result<TransactionsResponse, InvestecError> {
+        let url = self.base.join(&format!(
+            "za/pb/v1/accounts/{}/pending-transactions",
+            account_id
+        ))?;
+        let api_response: ApiResponse<TransactionsResponse> = self
+            .get_json(url, &[], &format!("account {}", account_id))
+            .await?;
+        Ok(api_response.data)
+    }
+
+    pub async fn get_account_balance(&self, account_id: &str) -> Result<Balance, InvestecError> {
+        let url = self
+            .base
+            .join(&format!("za/pb/v1/accounts/{}/balance", account_id))?;
+        let api_response: ApiResponse<Balance> = self
+            .get_json(url, &[], &format!("account {}", account_id))
+            .await?;
+        Ok(api_response.data)
+    }
+
+    pub async fn get_beneficiaries(&self) -> Result<Vec<Beneficiary>, InvestecError> {
+        let url = self.base.join("za/pb/v1/accounts/beneficiaries")?;
+        let api_response: ApiResponse<Vec<Beneficiary>> =
+            self.get_json(url, &[], "beneficiaries").await?;
+        Ok(api_response.data)
+    }
+
+    pub async fn get_beneficiary_categories(
+        &self,
+    ) -> Result<Vec<BeneficiaryCategory>, InvestecError> {
+        let url = self.base.join("za/pb/v1/accounts/beneficiarycategories")?;
+        let api_response: ApiResponse<Vec<BeneficiaryCategory>> =
+            self.get_json(url, &[], "beneficiary categories").await?;
+        Ok(api_response.data)
+    }
+
+    /// Moves money from `account_id` to other accounts on the same profile.
+    pub async fn transfer(
+        &self,
+        account_id: &str,
+        transfers: &[TransferInstruction],
+    ) -> Result<Vec<TransferResult>, InvestecError> {
+        self.ensure_payments_enabled()?;
+        let url = self.base.join(&format!(
+            "za/pb/v1/accounts/{}/transfermultiple",
+            account_id
+        ))?;
+        let request = TransferRequest {
+            transfer_list: transfers,
+        };
+        let api_response: ApiResponse<TransferResponse> = self
+            .post_json(url, &request, &format!("account {}", account_id))
+            .await?;
+        api_response.data.into_results()
+    }
+
+    /// Pays saved beneficiaries from `account_id`.
+    pub async fn pay_beneficiaries(
+        &self,
+        account_id: &str,
+        payments: &[PaymentInstruction],
+    ) -> Result<Vec<TransferResult>, InvestecError> {
+        self.ensure_payments_enabled()?;
+        let url = self
+            .base
+            .join(&format!("za/pb/v1/accounts/{}/paymultiple", account_id))?;
+        let request = PaymentRequest {
+            payment_list: payments,
+        };
+        let api_response: ApiResponse<TransferResponse> = self
+            .post_json(url, &request, &format!("account {}", account_id))
+            .await?;
+        api_response.data.into_results()
+    }
+
+    pub async fn get_cards(&self) -> Result<Vec<Card>, InvestecError> {
+        self.ensure_cards_enabled()?;
+        let url = self.base.join("za/v1/cards")?;
+        let api_response: ApiResponse<CardsResponse> = self.get_json(url, &[], "cards").await?;
+        Ok(api_response.data.cards)
+    }
+
+    /// The code saved on the card, which may not have been published yet.
+    pub async fn get_card_code(&self, card_key: &str) -> Result<CardCode, InvestecError> {
+        self.card_code(card_key, "code").await
+    }
+
+    /// The code that runs on the card's transactions.
+    pub async fn get_published_card_code(&self, card_key: &str) -> Result<CardCode, InvestecError> {
+        self.card_code(card_key, "publishedcode").await
+    }
+
+    async fn card_code(&self, card_key: &str, path: &str) -> Result<CardCode, InvestecError> {
+        self.ensure_cards_enabled()?;
+        let url = self
+            .base
+            .join(&format!("za/v1/cards/{}/{}", card_key, path))?;
+        let api_response: ApiResponse<CardResult<CardCode>> = self
+            .get_json(url, &[], &format!("card {}", card_key))
+            .await?;
+        Ok(api_response.data.result)
+    }
+
+    /// Saves code to the card without running it on transactions.
+    pub async fn save_card_code(
+        &self,
+        card_key: &str,
+        code: &str,
+    ) -> Result<CardCode, InvestecError> {
+        self.ensure_cards_enabled()?;
+        let url = self.base.join(&format!("za/v1/cards/{}/code", card_key))?;
+        let api_response: ApiResponse<CardResult<CardCode>> = self
+            .post_json(
+                url,
+                &SaveCodeRequest { code },
+                &format!("card {}", card_key),
+            )
+            .await?;
+        Ok(api_response.data.result)
+    }
+
+    /// Publishes saved code so it runs on the card's transactions.
+    pub async fn publish_card_code(
+        &self,
+        card_key: &str,
+        code_id: &str,
+        code: &str,
+    ) -> Result<CardCode, InvestecError> {
+        self.ensure_cards_enabled()?;
+        let url = self
+            .base
+            .join(&format!("za/v1/cards/{}/publish", card_key))?;
+        let api_response: ApiResponse<CardResult<CardCode>> = self
+            .post_json(
+                url,
+                &PublishCodeRequest { code_id, code },
+                &format!("card {}", card_key),
+            )
+            .await?;
+        Ok(api_response.data.result)
+    }
+
+    /// The environment variables card code on the card can read.
+    pub async fn get_card_environment(
+        &self,
+        card_key: &str,
+    ) -> Result<CardEnvironment, InvestecError> {
+        self.ensure_cards_enabled()?;
+        let url = self
+            .base
+            .join(&format!("za/v1/cards/{}/environmentvariables", card_key))?;
+        let api_response: ApiResponse<CardResult<CardEnvironment>> = self
+            .get_json(url, &[], &format!("card {}", card_key))
+            .await?;
+        Ok(api_response.data.result)
+    }
+
+    /// Replaces every environment variable on the card.
+    pub async fn save_card_environment(
+        &self,
+        card_key: &str,
+        environment: &CardEnvironment,
+    ) -> Result<CardEnvironment, InvestecError> {
+        self.ensure_cards_enabled()?;
+        let url = self
+            .base
+            .join(&format!("za/v1/cards/{}/environmentvariables", card_key))?;
+        let api_response: ApiResponse<CardResult<CardEnvironment>> = self
+            .post_json(url, environment, &format!("card {}", card_key))
+            .await?;
+        Ok(api_response.data.result)
+    }
+
+    /// Turns code execution on the card on or off, returning the new state.
+    pub async fn toggle_programmable_feature(
+        &self,
+        card_key: &str,
+        enabled: bool,
+    ) -> Result<bool, InvestecError> {
+        self.ensure_cards_enabled()?;
+        let url = self.base.join(&format!(
+            "za/v1/cards/{}/toggle-programmable-feature",
+            card_key
+        ))?;
+        let api_response: ApiResponse<CardResult<ProgrammableFeature>> = self
+            .post_json(
+                url,
+                &ProgrammableFeature { enabled },
+                &format!("card {}", card_key),
+            )
+            .await?;
+        Ok(api_response.data.result.enabled)
+    }
+
+    /// Runs code against a simulated authorisation on the card.
+    pub async fn execute_card_code(
+        &self,
+        card_key: &str,
+        simulation: &Simulation,
+    ) -> Result<Vec<CodeExecution>, InvestecError> {
+        self.ensure_cards_enabled()?;
+        let url = self
+            .base
+            .join(&format!("za/v1/cards/{}/code/execute", card_key))?;
+        let api_response: ApiResponse<CardResult<Vec<CodeExecution>>> = self
+            .post_json(url, simulation, &format!("card {}", card_key))
+            .await?;
+        Ok(api_response.data.result)
+    }
+
+    fn ensure_cards_enabled(&self) -> Result<(), InvestecError> {
+        if self.cards_enabled {
+            Ok(())
+        } else {
+            Err(InvestecError::CardsDisabled)
+        }
+    }
+
+    fn ensure_payments_enabled(&self) -> Result<(), InvestecError> {
+        if self.payments_enabled {
+            Ok(())
+        } else {
+            Err(InvestecError::PaymentsDisabled)
+        }
+    }
 
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        url: Url,
+        query: &[(&str, &str)],
+        resource: &str,
+    ) -> Result<T, InvestecError> {
+        let endpoint = monitoring::endpoint_label(url.path());
         let response = self
-            .http
-            .get(url)
-            .header(API_KEY_HEADER, &self.authenticator.api_key)
+            .send_with_retry(&endpoint, true, || self.http.get(url.clone()).query(query))
+            .await?;
+        decode_response(response, resource).await
+    }
+
+    /// POSTs are not idempotent, so they are only retried when the request
+    /// can't have reached Investec.
+    async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        url: Url,
+        body: &B,
+        resource: &str,
+    ) -> Result<T, InvestecError> {
+        let endpoint = monitoring::endpoint_label(url.path());
+        let response = self
+            .send_with_retry(&endpoint, false, || self.http.post(url.clone()).json(body))
+            .await?;
+        decode_response(response, resource).await
+    }
+
+    /// Sends a request, retrying 429s (and, for idempotent requests, timeouts
+    /// and 5xx responses) with backoff and re-authenticating once if the
+    /// cached token is rejected.
+    #[tracing::instrument(skip(self, request), fields(profile = %self.profile))]
+    async fn send_with_retry(
+        &self,
+        endpoint: &str,
+        idempotent: bool,
+        request: impl Fn() -> RequestBuilder,
+    ) -> Result<Response, InvestecError> {
+        self.circuit_breaker.check()?;
+
+        let mut attempt = 1;
+        let mut reauthenticated = false;
+
+        loop {
+            let started = Instant::now();
+            let sent = self.send_once(request()).await;
+            let status = match &sent {
+                Ok(response) => response.status().as_str().to_string(),
+                Err(_) => "error".to_string(),
+            };
+            monitoring::investec_request(endpoint, &status, started.elapsed());
+
+            let retry_after = match sent {
+                Ok(response)
+                    if response.status() == StatusCode::UNAUTHORIZED && !reauthenticated =>
+                {
+                    tracing::info!("Investec rejected the access token, re-authenticating");
+                    self.authenticator.invalidate_token();
+                    reauthenticated = true;
+                    continue;
+                }
+                Ok(response) if response.status() == StatusCode::UNAUTHORIZED => {
+                    // A fresh token was rejected too: the credentials are
+                    // wrong, which says nothing about whether the API is up.
+                    return Ok(response);
+                }
+                Ok(response)
+                    if retry::is_retryable_status(response.status())
+                        && (idempotent || response.status() == StatusCode::TOO_MANY_REQUESTS) =>
+                {
+                    if attempt >= self.retry_policy.max_attempts {
+                        self.circuit_breaker.record_failure();
+                        return Ok(response);
+                    }
+                    tracing::warn!(
+                        status = %response.status(),
+                        attempt,
+                        "Investec API request failed, retrying"
+                    );
+                    retry_after(&response)
+                }
+                Ok(response) => {
+                    if response.status().is_server_error() {
+                        self.circuit_breaker.record_failure();
+                    } else {
+                        self.circuit_breaker.record_success();
+                    }
+                    return Ok(response);
+                }
+                Err(e) if is_retryable_error(&e, idempotent) => {
+                    if attempt >= self.retry_policy.max_attempts {
+                        self.circuit_breaker.record_failure();
+                        return Err(e);
+                    }
+                    tracing::warn!(attempt, error = %e, "Investec API request failed, retrying");
+                    None
+                }
+                Err(e) => return Err(e),
+            };
+
+            tokio::time::sleep(self.retry_policy.delay_for(attempt, retry_after)).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send_once(&self, request: RequestBuilder) -> Result<Response, InvestecError> {
+        let token = self.authenticator.get_valid_token().await?;
+
+        let response = request
+            .header(API_KEY_HEADER, self.authenticator.api_key.expose())
             .bearer_auth(token)
-            .query(&[("fromDate", from_date), ("toDate", to_date)])
             .send()
             .await?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await?;
-            return Err(anyhow::anyhow!(
-                "Transactions API request failed with status {}: {}",
-                status,
-                body
+        Ok(response)
+    }
+}
+
+async fn decode_response<T: DeserializeOwned>(
+    response: Response,
+    resource: &str,
+) -> Result<T, InvestecError> {
+    let status = response.status();
+    let retry_after = retry_after(&response);
+    let body = response.text().await?;
+
+    if !status.is_success() {
+        return Err(InvestecError::from_status(
+            status,
+            &body,
+            retry_after,
+            resource,
+        ));
+    }
+
+    serde_json::from_str(&body).map_err(|e| InvestecError::decode(e, &body))
+}
+
+/// Token endpoint outages surface as errors rather than responses and, like
+/// failed connections, mean the request itself was never sent.
+fn is_retryable_error(error: &InvestecError, idempotent: bool) -> bool {
+    match error {
+        InvestecError::Server { .. } | InvestecError::RateLimited { .. } => true,
+        InvestecError::Network(e) if e.is_connect() => true,
+        e => idempotent && e.is_transient(),
+    }
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| retry::parse_retry_after(value, Utc::now()))
+}
+
+/// `Url::join` replaces the last path segment unless the base ends in `/`,
+/// which matters for base URLs with a path prefix such as a local mock.
+fn with_trailing_slash(url: &str) -> String {
+    if url.ends_with('/') {
+        url.to_string()
+    } else {
+        format!("{}/", url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_investec::{self, MockState, fixtures};
+    use std::sync::Arc;
+
+    async fn mock_client() -> InvestecClient {
+        mock_client_with_state().await.0
+    }
+
+    async fn mock_client_with_state() -> (InvestecClient, Arc<MockState>) {
+        mock_client_with(MockState::new(fixtures::Fixtures::seed(
+            chrono::Utc::now().date_naive(),
+        )))
+        .await
+    }
+
+    async fn mock_client_with(state: MockState) -> (InvestecClient, Arc<MockState>) {
+        let state = Arc::new(state);
+        let (addr, _) = mock_investec::spawn("127.0.0.1:0", state.clone())
+            .await
+            .unwrap();
+        let mut config = Config::for_tests();
+        config.investec.base_url = format!("http://{}", addr);
+        (InvestecClient::new(config).unwrap(), state)
+    }
+
+    #[tokio::test]
+    async fn test_get_accounts_and_balance_from_mock() {
+        let client = mock_client().await;
+
+        let accounts = client.get_accounts().await.unwrap();
+        assert_eq!(accounts.len(), 2);
+
+        let balance = client
+            .get_account_balance(fixtures::CHEQUE_ACCOUNT_ID)
+            .await
+            .unwrap();
+        assert_eq!(balance.currency, "ZAR");
+    }
+
+    #[tokio::test]
+    async fn test_get_transactions_filters_by_date_from_mock() {
+        let client = mock_client().await;
+        let today = chrono::Utc::now()
+            .date_naive()
+            .format("%Y-%m-%d")
+            .to_string();
+
+        let response = client
+            .get_transactions(
+                fixtures::CHEQUE_ACCOUNT_ID,
+                &TransactionQuery::new().since(&today).until(&today),
+            )
+            .await
+            .unwrap();
+
+        assert!(!response.transactions.is_empty());
+        assert!(
+            response
+                .transactions
+                .iter()
+                .all(|tx| tx.transaction_date.as_deref() == Some(today.as_str()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_transactions_unknown_account_fails() {
+        let client = mock_client().await;
+
+        let error = client
+            .get_transactions("missing", &TransactionQuery::new())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, InvestecError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_retries_server_errors_and_rate_limits() {
+        let (client, state) = mock_client_with_state().await;
+        state.inject_failure(StatusCode::SERVICE_UNAVAILABLE, None);
+        state.inject_failure(StatusCode::TOO_MANY_REQUESTS, Some(0));
+
+        let accounts = client.get_accounts().await.unwrap();
+        assert_eq!(accounts.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts_and_opens_circuit() {
+        let (client, state) = mock_client_with_state().await;
+        for _ in 0..9 {
+            state.inject_failure(StatusCode::INTERNAL_SERVER_ERROR, None);
+        }
+
+        for _ in 0..3 {
+            assert!(matches!(
+                client.get_accounts().await,
+                Err(InvestecError::Server { .. })
             ));
         }
 
-        let body = response.text().await?;
+        let error = client.get_accounts().await.unwrap_err();
+        assert!(matches!(error, InvestecError::CircuitOpen(_)));
+    }
 
-        let api_response: ApiResponse<TransactionsResponse> = serde_json::from_str(&body)?;
-        Ok(api_response.data)
+    #[tokio::test]
+    async fn test_rejected_credentials_do_not_reset_circuit() {
+        let (client, state) = mock_client_with_state().await;
+        let fail_all_attempts = || {
+            for _ in 0..3 {
+                state.inject_failure(StatusCode::INTERNAL_SERVER_ERROR, None);
+            }
+        };
+
+        for _ in 0..2 {
+            fail_all_attempts();
+            assert!(client.get_accounts().await.is_err());
+        }
+        state.inject_failure(StatusCode::UNAUTHORIZED, None);
+        state.inject_failure(StatusCode::UNAUTHORIZED, None);
+        assert!(matches!(
+            client.get_accounts().await,
+            Err(InvestecError::Auth { .. })
+        ));
+        fail_all_attempts();
+        assert!(client.get_accounts().await.is_err());
+
+        let error = client.get_accounts().await.unwrap_err();
+        assert!(matches!(error, InvestecError::CircuitOpen(_)));
+    }
+
+    #[tokio::test]
+    async fn test_reauthenticates_when_token_is_rejected() {
+        let (client, state) = mock_client_with_state().await;
+        client.get_accounts().await.unwrap();
+
+        state.revoke_tokens();
+
+        let accounts = client.get_accounts().await.unwrap();
+        assert_eq!(accounts.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_transactions_follows_pages() {
+        let seeded = || fixtures::Fixtures::seed(chrono::Utc::now().date_naive());
+        let (paged, _) = mock_client_with(MockState::new(seeded()).with_page_size(3)).await;
+        let (unpaged, _) = mock_client_with(MockState::new(seeded())).await;
+
+        let from = (chrono::Utc::now().date_naive() - chrono::Duration::days(90))
+            .format("%Y-%m-%d")
+            .to_string();
+        let query = TransactionQuery::new().since(from);
+        let all = unpaged
+            .get_transactions(fixtures::CHEQUE_ACCOUNT_ID, &query)
+            .await
+            .unwrap()
+            .transactions;
+        let followed = paged
+            .get_transactions(fixtures::CHEQUE_ACCOUNT_ID, &query)
+            .await
+            .unwrap()
+            .transactions;
+
+        assert!(all.len() > 3);
+        assert_eq!(
+            followed.iter().map(|tx| &tx.uuid).collect::<Vec<_>>(),
+            all.iter().map(|tx| &tx.uuid).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transaction_type_filter_and_pending() {
+        let client = mock_client().await;
+
+        let card_purchases = client
+            .get_transactions(
+                fixtures::CHEQUE_ACCOUNT_ID,
+                &TransactionQuery::new().transaction_type("CardPurchases"),
+            )
+            .await
+            .unwrap()
+            .transactions;
+        assert!(!card_purchases.is_empty());
+        assert!(
+            card_purchases
+                .iter()
+                .all(|tx| tx.transaction_type.as_deref() == Some("CardPurchases"))
+        );
+
+        let pending = client
+            .get_pending_transactions(fixtures::CHEQUE_ACCOUNT_ID)
+            .await
+            .unwrap()
+            .transactions;
+        assert!(!pending.is_empty());
+        assert!(pending.iter().all(|tx| tx.status == "PENDING"));
+
+        let with_pending = client
+            .get_transactions(
+                fixtures::CHEQUE_ACCOUNT_ID,
+                &TransactionQuery::new().include_pending(true),
+            )
+            .await
+            .unwrap()
+            .transactions;
+        assert!(with_pending.iter().any(|tx| tx.status == "PENDING"));
+    }
+
+    #[tokio::test]
+    async fn test_transfer_and_pay_beneficiary() {
+        let (client, state) = mock_client_with_state().await;
+
+        let beneficiaries = client.get_beneficiaries().await.unwrap();
+        assert_eq!(beneficiaries.len(), 2);
+        assert!(
+            !client
+                .get_beneficiary_categories()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let transfer =
+            TransferInstruction::new(fixtures::SAVINGS_ACCOUNT_ID, 150.5, "sweep", "sweep");
+        let results = client
+            .transfer(fixtures::CHEQUE_ACCOUNT_ID, &[transfer])
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+
+        let payment =
+            PaymentInstruction::new(&beneficiaries[0].beneficiary_id, 10.0, "rent", "rent");
+        client
+            .pay_beneficiaries(fixtures::CHEQUE_ACCOUNT_ID, &[payment])
+            .await
+            .unwrap();
+
+        let payments = state.payments();
+        assert_eq!(payments.len(), 2);
+        assert_eq!(payments[0]["instruction"]["amount"], "150.50");
+
+        let unknown = PaymentInstruction::new("nobody", 10.0, "rent", "rent");
+        assert!(matches!(
+            client
+                .pay_beneficiaries(fixtures::CHEQUE_ACCOUNT_ID, &[unknown])
+                .await,
+            Err(InvestecError::PaymentRejected { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_payments_require_opt_in() {
+        let (addr, _) = mock_investec::spawn(
+            "127.0.0.1:0",
+            Arc::new(MockState::new(fixtures::Fixtures::seed(
+                chrono::Utc::now().date_naive(),
+            ))),
+        )
+        .await
+        .unwrap();
+        let mut config = Config::for_tests();
+        config.investec.base_url = format!("http://{}", addr);
+        config.investec.payments_enabled = false;
+        let client = InvestecClient::new(config).unwrap();
+
+        let transfer = TransferInstruction::new(fixtures::SAVINGS_ACCOUNT_ID, 1.0, "x", "x");
+        assert!(matches!(
+            client
+                .transfer(fixtures::CHEQUE_ACCOUNT_ID, &[transfer])
+                .await,
+            Err(InvestecError::PaymentsDisabled)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_card_code_save_publish_and_simulate() {
+        let client = mock_client().await;
+
+        let cards = client.get_cards().await.unwrap();
+        assert_eq!(cards.len(), 1);
+        let card_key = &cards[0].card_key;
+
+        let code = "const beforeTransaction = async (authorization) => false;\n";
+        let saved = client.save_card_code(card_key, code).await.unwrap();
+        assert_eq!(client.get_card_code(card_key).await.unwrap().code, code);
+        assert_ne!(
+            client.get_published_card_code(card_key).await.unwrap().code,
+            code
+        );
+
+        client
+            .publish_card_code(card_key, &saved.code_id, code)
+            .await
+            .unwrap();
+        assert_eq!(
+            client.get_published_card_code(card_key).await.unwrap().code,
+            code
+        );
+
+        assert!(
+            !client
+                .toggle_programmable_feature(card_key, false)
+                .await
+                .unwrap()
+        );
+
+        let simulation = Simulation {
+            code: code.to_string(),
+            cents_amount: "12550".to_string(),
+            currency_code: "zar".to_string(),
+            merchant_code: 5812,
+            merchant_name: "The Coders Bakery".to_string(),
+            merchant_city: "Cape Town".to_string(),
+            country_code: "ZA".to_string(),
+        };
+        let executions = client
+            .execute_card_code(card_key, &simulation)
+            .await
+            .unwrap();
+        assert_eq!(executions[0].type_, "before_transaction");
+
+        assert!(matches!(
+            client.get_card_code("no-such-card").await,
+            Err(InvestecError::NotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_card_environment_round_trip() {
+        let client = mock_client().await;
+        let card_key = &client.get_cards().await.unwrap()[0].card_key;
+        assert!(
+            client
+                .get_card_environment(card_key)
+                .await
+                .unwrap()
+                .variables
+                .is_empty()
+        );
+
+        let mut environment = CardEnvironment::default();
+        environment
+            .variables
+            .insert("CARD_WEBHOOK_SECRET".to_string(), "s3cret".into());
+        client
+            .save_card_environment(card_key, &environment)
+            .await
+            .unwrap();
+
+        let saved = client.get_card_environment(card_key).await.unwrap();
+        assert_eq!(saved.variables["CARD_WEBHOOK_SECRET"], "s3cret");
+        assert!(!format!("{:?}", saved).contains("s3cret"));
     }
 }
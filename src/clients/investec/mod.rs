@@ -1,4 +1,9 @@
 pub mod auth;
+pub mod errors;
 pub mod investec;
 pub mod models;
+pub mod query;
+pub mod retry;
+pub use errors::InvestecError;
 pub use investec::InvestecClient;
+pub use query::TransactionQuery;
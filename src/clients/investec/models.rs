@@ -1,4 +1,6 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use super::errors::InvestecError;
 
 #[derive(Debug, Deserialize)]
 pub struct TokenResponse {
@@ -34,6 +36,32 @@ pub struct AccountsResponse {
 #[derive(Debug, Deserialize)]
 pub struct ApiResponse<T> {
     pub data: T,
+    #[serde(default)]
+    pub links: Links,
+    #[serde(default)]
+    pub meta: Meta,
+}
+
+impl<T> ApiResponse<T> {
+    /// Whether another page follows `page` (1-based). Falls back to the
+    /// presence of a `next` link when `totalPages` is missing.
+    pub fn has_more_pages(&self, page: u32) -> bool {
+        match self.meta.total_pages {
+            Some(total_pages) => page < total_pages,
+            None => self.links.next.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Links {
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Meta {
+    #[serde(rename = "totalPages")]
+    pub total_pages: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -98,3 +126,220 @@ pub struct Transaction {
 pub struct TransactionsResponse {
     pub transactions: Vec<Transaction>,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct Beneficiary {
+    #[serde(rename = "beneficiaryId")]
+    pub beneficiary_id: String,
+    pub bank: Option<String>,
+    #[serde(rename = "beneficiaryName")]
+    pub beneficiary_name: String,
+    #[serde(rename = "categoryId")]
+    pub category_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BeneficiaryCategory {
+    pub id: String,
+    pub name: String,
+}
+
+/// A transfer between two of the profile's own accounts.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferInstruction {
+    #[serde(rename = "beneficiaryAccountId")]
+    pub beneficiary_account_id: String,
+    /// Rand amount formatted with two decimals, as the API expects a string.
+    pub amount: String,
+    #[serde(rename = "myReference")]
+    pub my_reference: String,
+    #[serde(rename = "theirReference")]
+    pub their_reference: String,
+}
+
+impl TransferInstruction {
+    pub fn new(
+        to_account_id: &str,
+        amount: f64,
+        my_reference: &str,
+        their_reference: &str,
+    ) -> Self {
+        Self {
+            beneficiary_account_id: to_account_id.to_string(),
+            amount: format!("{:.2}", amount),
+            my_reference: my_reference.to_string(),
+            their_reference: their_reference.to_string(),
+        }
+    }
+}
+
+/// A payment to a saved beneficiary.
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentInstruction {
+    #[serde(rename = "beneficiaryId")]
+    pub beneficiary_id: String,
+    pub amount: String,
+    #[serde(rename = "myReference")]
+    pub my_reference: String,
+    #[serde(rename = "theirReference")]
+    pub their_reference: String,
+}
+
+impl PaymentInstruction {
+    pub fn new(
+        beneficiary_id: &str,
+        amount: f64,
+        my_reference: &str,
+        their_reference: &str,
+    ) -> Self {
+        Self {
+            beneficiary_id: beneficiary_id.to_string(),
+            amount: format!("{:.2}", amount),
+            my_reference: my_reference.to_string(),
+            their_reference: their_reference.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransferRequest<'a> {
+    #[serde(rename = "transferList")]
+    pub transfer_list: &'a [TransferInstruction],
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentRequest<'a> {
+    #[serde(rename = "paymentList")]
+    pub payment_list: &'a [PaymentInstruction],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferResult {
+    #[serde(rename = "PaymentReferenceNumber")]
+    pub payment_reference_number: String,
+    #[serde(rename = "Status")]
+    pub status: String,
+    #[serde(rename = "AuthorisationRequired")]
+    pub authorisation_required: bool,
+}
+
+/// Shared response shape of the transfer and payment endpoints.
+#[derive(Debug, Deserialize)]
+pub struct TransferResponse {
+    #[serde(rename = "TransferResponses", default)]
+    pub results: Vec<TransferResult>,
+    #[serde(rename = "ErrorMessage")]
+    pub error_message: Option<String>,
+}
+
+impl TransferResponse {
+    pub fn into_results(self) -> Result<Vec<TransferResult>, InvestecError> {
+        match self.error_message {
+            Some(message) if !message.is_empty() => Err(InvestecError::PaymentRejected { message }),
+            _ => Ok(self.results),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Card {
+    #[serde(rename = "CardKey")]
+    pub card_key: String,
+    #[serde(rename = "CardNumber")]
+    pub card_number: String,
+    #[serde(rename = "IsProgrammable")]
+    pub is_programmable: bool,
+    #[serde(rename = "Status")]
+    pub status: String,
+    #[serde(rename = "AccountId")]
+    pub account_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CardsResponse {
+    pub cards: Vec<Card>,
+}
+
+/// Wrapper the card code endpoints put around their payload.
+#[derive(Debug, Deserialize)]
+pub struct CardResult<T> {
+    pub result: T,
+}
+
+/// A saved or published version of a card's `main.js`.
+#[derive(Debug, Deserialize)]
+pub struct CardCode {
+    #[serde(rename = "codeId")]
+    pub code_id: String,
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SaveCodeRequest<'a> {
+    pub code: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublishCodeRequest<'a> {
+    #[serde(rename = "codeid")]
+    pub code_id: &'a str,
+    pub code: &'a str,
+}
+
+/// Variables card code reads as `process.env`. Values may be secrets, so
+/// `Debug` only lists the names.
+#[derive(Default, Serialize, Deserialize)]
+pub struct CardEnvironment {
+    pub variables: serde_json::Map<String, serde_json::Value>,
+}
+
+impl std::fmt::Debug for CardEnvironment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CardEnvironment")
+            .field("variables", &self.variables.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProgrammableFeature {
+    #[serde(rename = "Enabled")]
+    pub enabled: bool,
+}
+
+/// A card authorisation to run code against without a real purchase.
+#[derive(Debug, Clone, Serialize)]
+pub struct Simulation {
+    #[serde(rename = "simulationcode")]
+    pub code: String,
+    /// Whole cents as a string, as the API expects.
+    #[serde(rename = "centsAmount")]
+    pub cents_amount: String,
+    #[serde(rename = "currencyCode")]
+    pub currency_code: String,
+    #[serde(rename = "merchantCode")]
+    pub merchant_code: u32,
+    #[serde(rename = "merchantName")]
+    pub merchant_name: String,
+    #[serde(rename = "merchantCity")]
+    pub merchant_city: String,
+    #[serde(rename = "countryCode")]
+    pub country_code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CodeExecution {
+    /// `before_transaction`, `after_transaction`, ...
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(rename = "authorizationApproved")]
+    pub authorization_approved: Option<bool>,
+    #[serde(default)]
+    pub logs: Vec<CodeLog>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CodeLog {
+    pub level: String,
+    pub content: String,
+}
@@ -0,0 +1,88 @@
+/// Filters for the transactions endpoint, built up with chained setters:
+///
+/// ```ignore
+/// let query = TransactionQuery::new()
+///     .since("2024-01-01")
+///     .until("2024-01-31")
+///     .transaction_type("CardPurchases")
+///     .include_pending(true);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionQuery {
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    pub transaction_type: Option<String>,
+    pub include_pending: bool,
+}
+
+impl TransactionQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn since(mut self, date: impl Into<String>) -> Self {
+        self.from_date = Some(date.into());
+        self
+    }
+
+    pub fn until(mut self, date: impl Into<String>) -> Self {
+        self.to_date = Some(date.into());
+        self
+    }
+
+    /// Restricts results to one Investec transaction type, e.g. `CardPurchases`.
+    pub fn transaction_type(mut self, transaction_type: impl Into<String>) -> Self {
+        self.transaction_type = Some(transaction_type.into());
+        self
+    }
+
+    /// Also returns transactions that haven't posted yet.
+    pub fn include_pending(mut self, include_pending: bool) -> Self {
+        self.include_pending = include_pending;
+        self
+    }
+
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(from_date) = &self.from_date {
+            params.push(("fromDate", from_date.clone()));
+        }
+        if let Some(to_date) = &self.to_date {
+            params.push(("toDate", to_date.clone()));
+        }
+        if let Some(transaction_type) = &self.transaction_type {
+            params.push(("transactionType", transaction_type.clone()));
+        }
+        if self.include_pending {
+            params.push(("includePending", "true".to_string()));
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_only_include_set_filters() {
+        assert!(TransactionQuery::new().params().is_empty());
+
+        let params = TransactionQuery::new()
+            .since("2024-01-01")
+            .until("2024-01-31")
+            .transaction_type("CardPurchases")
+            .include_pending(true)
+            .params();
+
+        assert_eq!(
+            params,
+            vec![
+                ("fromDate", "2024-01-01".to_string()),
+                ("toDate", "2024-01-31".to_string()),
+                ("transactionType", "CardPurchases".to_string()),
+                ("includePending", "true".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use crate::config::settings::InvestecConfig;
+
+/// Longest `Retry-After` honoured; anything longer is left to the next sync.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &InvestecConfig) -> Self {
+        Self {
+            max_attempts: config.retry_max_attempts.max(1),
+            base_delay: Duration::from_millis(config.retry_base_delay_ms),
+            max_delay: Duration::from_millis(config.retry_max_delay_ms),
+        }
+    }
+
+    /// Exponential backoff capped at `max_delay`, with the upper half of each
+    /// step randomised. A server supplied `Retry-After` takes precedence.
+    pub fn delay_for(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(MAX_RETRY_AFTER);
+        }
+
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        let jitter_ms = half.as_millis() as u64;
+        let jitter = if jitter_ms == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(rand::random_range(0..=jitter_ms))
+        };
+
+        half + jitter
+    }
+}
+
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parses a `Retry-After` header given either as seconds or an HTTP date.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[derive(Debug, Error)]
+#[error("Investec API circuit breaker is open, retrying in {}s", retry_in.as_secs())]
+pub struct CircuitOpenError {
+    pub retry_in: Duration,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// When the half-open trial request was let through.
+    trial_started_at: Option<Instant>,
+}
+
+/// Stops calling the API for `cooldown` after `failure_threshold` consecutive
+/// failed requests, so a prolonged outage fails fast instead of retrying.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState {
+                consecutive_failures: 0,
+                open_until: None,
+                trial_started_at: None,
+            }),
+        }
+    }
+
+    /// Errors while open. Once the cooldown has passed a single trial request
+    /// is let through; its outcome closes or re-opens the breaker. A trial
+    /// that never reports back is replaced after another cooldown.
+    pub fn check(&self) -> Result<(), CircuitOpenError> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let Some(open_until) = state.open_until else {
+            return Ok(());
+        };
+        if open_until > now {
+            return Err(CircuitOpenError {
+                retry_in: open_until - now,
+            });
+        }
+
+        match state.trial_started_at {
+            Some(started) if now - started < self.cooldown => Err(CircuitOpenError {
+                retry_in: self.cooldown - (now - started),
+            }),
+            _ => {
+                state.trial_started_at = Some(now);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.open_until.is_some() {
+            tracing::info!("Investec API recovered, closing circuit breaker");
+        }
+        state.consecutive_failures = 0;
+        state.open_until = None;
+        state.trial_started_at = None;
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+
+        let half_open = state.open_until.is_some();
+        if half_open || state.consecutive_failures >= self.failure_threshold {
+            tracing::warn!(
+                failures = state.consecutive_failures,
+                cooldown_secs = self.cooldown.as_secs(),
+                "Opening Investec API circuit breaker"
+            );
+            state.open_until = Some(Instant::now() + self.cooldown);
+            state.trial_started_at = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        }
+    }
+
+    #[test]
+    fn test_delay_grows_exponentially_and_is_capped() {
+        let policy = policy();
+
+        let third = policy.delay_for(3, None);
+        assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+
+        let tenth = policy.delay_for(10, None);
+        assert!(tenth >= Duration::from_millis(500) && tenth <= Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_retry_after_overrides_backoff() {
+        assert_eq!(
+            policy().delay_for(1, Some(Duration::from_secs(7))),
+            Duration::from_secs(7)
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2024-01-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(parse_retry_after("30", now), Some(Duration::from_secs(30)));
+        assert_eq!(
+            parse_retry_after("Mon, 01 Jan 2024 10:01:00 GMT", now),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+
+        breaker.record_success();
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn test_circuit_breaker_lets_one_trial_through() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_err());
+
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.check().is_ok());
+        breaker.record_success();
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_ok());
+    }
+}
@@ -27,4 +27,10 @@ impl OllamaClient {
         let response = self.ollama.send_chat_messages(request).await?;
         Ok(response.message.content.trim().to_string())
     }
+
+    /// Checks that the Ollama server answers.
+    pub async fn ping(&self) -> Result<()> {
+        self.ollama.list_local_models().await?;
+        Ok(())
+    }
 }
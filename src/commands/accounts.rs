@@ -0,0 +1,68 @@
+use clap::Args;
+
+use crate::clients::InvestecClient;
+use crate::config::settings::Config;
+
+#[derive(Debug, Args)]
+pub struct AccountsArgs {
+    /// Also list transactions that haven't posted yet
+    #[arg(long)]
+    pending: bool,
+}
+
+pub async fn run(config: Config, args: AccountsArgs) -> anyhow::Result<()> {
+    let clients = InvestecClient::for_profiles(&config)?;
+    for (index, client) in clients.iter().enumerate() {
+        if clients.len() > 1 {
+            if index > 0 {
+                println!();
+            }
+            println!("Profile {}", client.profile());
+        }
+        print_accounts(client, args.pending).await?;
+    }
+
+    Ok(())
+}
+
+async fn print_accounts(client: &InvestecClient, pending: bool) -> anyhow::Result<()> {
+    let accounts = client.get_accounts().await?;
+
+    println!(
+        "{:<36} {:<14} {:<24} {:>14} {:>14}",
+        "Account id", "Number", "Product", "Current", "Available"
+    );
+    for account in &accounts {
+        let balance = client.get_account_balance(&account.account_id).await?;
+        println!(
+            "{:<36} {:<14} {:<24} {:>14.2} {:>14.2}",
+            account.account_id,
+            account.account_number,
+            account.product_name,
+            balance.current_balance,
+            balance.available_balance
+        );
+    }
+
+    if pending {
+        println!();
+        println!(
+            "{:<36} {:<12} {:<40} {:>12}",
+            "Account id", "Date", "Pending transaction", "Amount"
+        );
+        for account in &accounts {
+            let pending = client.get_pending_transactions(&account.account_id).await?;
+            for transaction in pending.transactions {
+                println!(
+                    "{:<36} {:<12} {:<40} {:>12.2}",
+                    account.account_id,
+                    transaction.transaction_date.unwrap_or_default(),
+                    transaction.description,
+                    transaction.amount
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
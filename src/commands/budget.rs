@@ -0,0 +1,102 @@
+use chrono::Utc;
+use clap::{Args, Subcommand};
+
+use crate::budgets::{self, BudgetPeriod};
+use crate::config::settings::Config;
+use crate::db;
+
+#[derive(Debug, Args)]
+pub struct BudgetArgs {
+    #[command(subcommand)]
+    command: BudgetCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum BudgetCommand {
+    /// Create or update the spending limit for a bucket
+    Set {
+        bucket: String,
+        amount: f64,
+        #[arg(long, value_enum, default_value_t = BudgetPeriod::Monthly)]
+        period: BudgetPeriod,
+    },
+    /// Show every budget with its spend for the current period
+    List,
+    /// Delete the budget for a bucket
+    Remove {
+        bucket: String,
+        #[arg(long, value_enum, default_value_t = BudgetPeriod::Monthly)]
+        period: BudgetPeriod,
+    },
+}
+
+pub async fn run(config: Config, args: BudgetArgs) -> anyhow::Result<()> {
+    let database = db::Database::initialize(&config.database).await?;
+
+    match args.command {
+        BudgetCommand::Set {
+            bucket,
+            amount,
+            period,
+        } => {
+            let bucket = known_bucket(&config, &bucket)?;
+            if !(amount.is_finite() && amount > 0.0) {
+                anyhow::bail!("Budget amount must be a number greater than zero");
+            }
+
+            db::upsert_budget(&database.pool, &bucket, period.as_str(), amount).await?;
+            println!("Set {} budget for {} to {:.2}", period, bucket, amount);
+        }
+        BudgetCommand::List => {
+            let statuses =
+                budgets::budget_statuses(&database.pool, Utc::now().date_naive()).await?;
+            if statuses.is_empty() {
+                println!("No budgets defined");
+                return Ok(());
+            }
+
+            println!(
+                "{:<20} {:<8} {:>12} {:>12} {:>8}  Since",
+                "Bucket", "Period", "Limit", "Spent", "Used"
+            );
+            for status in statuses {
+                println!(
+                    "{:<20} {:<8} {:>12.2} {:>12.2} {:>7.0}%  {}",
+                    status.budget.bucket,
+                    status.budget.period,
+                    status.budget.amount,
+                    status.spent,
+                    status.percent_used(),
+                    status.period_start
+                );
+            }
+        }
+        BudgetCommand::Remove { bucket, period } => {
+            let bucket = known_bucket(&config, &bucket)?;
+            if db::delete_budget(&database.pool, &bucket, period.as_str()).await? {
+                println!("Removed {} budget for {}", period, bucket);
+            } else {
+                println!("No {} budget found for {}", period, bucket);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves `bucket` case-insensitively against the configured buckets.
+pub fn known_bucket(config: &Config, bucket: &str) -> anyhow::Result<String> {
+    config
+        .buckets
+        .categories
+        .iter()
+        .find(|category| category.eq_ignore_ascii_case(bucket))
+        .cloned()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown bucket '{}', expected one of: {}",
+                bucket,
+                config.buckets.categories.join(", ")
+            )
+        })
+}
@@ -0,0 +1,397 @@
+use chrono::Utc;
+use clap::{Args, Subcommand};
+
+use super::budget::known_bucket;
+use crate::card_code::{self, EventForwarding};
+use crate::clients::InvestecClient;
+use crate::clients::investec::models::Simulation;
+use crate::config::secret::Secret;
+use crate::config::settings::Config;
+use crate::db;
+
+#[derive(Debug, Args)]
+pub struct CardArgs {
+    #[command(subcommand)]
+    command: CardCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum CardCommand {
+    /// List cards and whether they are programmable
+    List,
+    /// Manage the merchant categories declined when a bucket's budget runs out
+    Rule {
+        #[command(subcommand)]
+        command: RuleCommand,
+    },
+    /// Print the card code generated from budgets and card rules
+    Generate,
+    /// Diff two deployed versions, or a deployed version against the generated code
+    Diff {
+        card_key: String,
+        /// Version to diff from, defaults to the latest deployed
+        #[arg(long)]
+        from: Option<i32>,
+        /// Version to diff to, defaults to the generated code
+        #[arg(long)]
+        to: Option<i32>,
+    },
+    /// Save the generated code to a card
+    Deploy {
+        card_key: String,
+        /// Also publish the code so it runs on the card's transactions
+        #[arg(long)]
+        publish: bool,
+        /// Deploy instead of printing the diff
+        #[arg(long)]
+        yes: bool,
+    },
+    /// List the versions deployed to a card
+    History { card_key: String },
+    /// Print the code currently on a card
+    Show {
+        card_key: String,
+        /// Show the published code instead of the saved code
+        #[arg(long)]
+        published: bool,
+    },
+    /// Run the code on a card for its transactions
+    Enable { card_key: String },
+    /// Stop running code on a card's transactions
+    Disable { card_key: String },
+    /// Run the generated code against a simulated purchase
+    Simulate {
+        card_key: String,
+        amount: f64,
+        /// Merchant category code, e.g. 5812
+        #[arg(long)]
+        merchant_code: u32,
+        #[arg(long)]
+        merchant_name: String,
+        #[arg(long, default_value = "Cape Town")]
+        merchant_city: String,
+        #[arg(long, default_value = "ZA")]
+        country: String,
+        #[arg(long, default_value = "zar")]
+        currency: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum RuleCommand {
+    /// Decline purchases at merchant categories once the bucket's budget runs out
+    Add {
+        #[arg(long)]
+        bucket: String,
+        /// Merchant category code, repeat for several
+        #[arg(long = "category", required = true)]
+        categories: Vec<String>,
+    },
+    /// List card rules
+    List,
+    /// Delete a card rule
+    Remove { id: i32 },
+}
+
+pub async fn run(config: Config, args: CardArgs) -> anyhow::Result<()> {
+    let forwarding = EventForwarding::from_config(&config.card_webhook);
+
+    match args.command {
+        CardCommand::List => {
+            let client = InvestecClient::new(config)?;
+            let cards = client.get_cards().await?;
+            println!(
+                "{:<24} {:<18} {:<12} {:<10} Account",
+                "Card key", "Card number", "Programmable", "Status"
+            );
+            for card in cards {
+                println!(
+                    "{:<24} {:<18} {:<12} {:<10} {}",
+                    card.card_key,
+                    card.card_number,
+                    if card.is_programmable { "yes" } else { "no" },
+                    card.status,
+                    card.account_id
+                );
+            }
+        }
+        CardCommand::Rule { command } => {
+            let database = db::Database::initialize(&config.database).await?;
+            match command {
+                RuleCommand::Add { bucket, categories } => {
+                    let bucket = known_bucket(&config, &bucket)?;
+                    if let Some(invalid) = categories
+                        .iter()
+                        .find(|code| !card_code::is_merchant_category_code(code))
+                    {
+                        anyhow::bail!(
+                            "Invalid merchant category code '{}', expected four digits",
+                            invalid
+                        );
+                    }
+
+                    for category in categories {
+                        match db::insert_card_rule(&database.pool, &bucket, &category).await? {
+                            Some(id) => {
+                                println!("Added card rule {} for {} {}", id, bucket, category)
+                            }
+                            None => println!("{} already has a rule for {}", bucket, category),
+                        }
+                    }
+                }
+                RuleCommand::List => {
+                    let rules = db::list_card_rules(&database.pool).await?;
+                    if rules.is_empty() {
+                        println!("No card rules defined");
+                        return Ok(());
+                    }
+
+                    println!("{:>4}  {:<20} Category", "Id", "Bucket");
+                    for rule in rules {
+                        println!(
+                            "{:>4}  {:<20} {}",
+                            rule.id, rule.bucket, rule.merchant_category_code
+                        );
+                    }
+                }
+                RuleCommand::Remove { id } => {
+                    if db::delete_card_rule(&database.pool, id).await? {
+                        println!("Removed card rule {}", id);
+                    } else {
+                        println!("No card rule {}", id);
+                    }
+                }
+            }
+        }
+        CardCommand::Generate => {
+            let database = db::Database::initialize(&config.database).await?;
+            print!(
+                "{}",
+                card_code::generate(&database.pool, Utc::now().date_naive(), forwarding.as_ref())
+                    .await?
+            );
+        }
+        CardCommand::Diff { card_key, from, to } => {
+            let database = db::Database::initialize(&config.database).await?;
+            let (from_name, from_code) =
+                match db::find_card_code_deployment(&database.pool, &card_key, from).await? {
+                    Some(deployment) => (format!("v{}", deployment.version), deployment.code),
+                    None => match from {
+                        Some(version) => anyhow::bail!("{} has no version {}", card_key, version),
+                        None => ("(nothing deployed)".to_string(), String::new()),
+                    },
+                };
+            let (to_name, to_code) = match to {
+                Some(version) => {
+                    let Some(deployment) =
+                        db::find_card_code_deployment(&database.pool, &card_key, Some(version))
+                            .await?
+                    else {
+                        anyhow::bail!("{} has no version {}", card_key, version);
+                    };
+                    (format!("v{}", version), deployment.code)
+                }
+                None => (
+                    "generated".to_string(),
+                    card_code::generate(
+                        &database.pool,
+                        Utc::now().date_naive(),
+                        forwarding.as_ref(),
+                    )
+                    .await?,
+                ),
+            };
+
+            let diff = card_code::diff(&from_code, &to_code, &from_name, &to_name);
+            if diff.is_empty() {
+                println!("No changes between {} and {}", from_name, to_name);
+            } else {
+                print!("{}", diff);
+            }
+        }
+        CardCommand::Deploy {
+            card_key,
+            publish,
+            yes,
+        } => {
+            let database = db::Database::initialize(&config.database).await?;
+            let code =
+                card_code::generate(&database.pool, Utc::now().date_naive(), forwarding.as_ref())
+                    .await?;
+            let latest = db::find_card_code_deployment(&database.pool, &card_key, None).await?;
+            let secret = forwarding.and(config.card_webhook.secret.clone());
+
+            if let Some(latest) = &latest
+                && latest.code == code
+                && (latest.published || !publish)
+            {
+                // The secret may have been rotated without changing the code.
+                if yes && let Some(secret) = &secret {
+                    let client = InvestecClient::new(config)?;
+                    store_webhook_secret(&client, &card_key, secret).await?;
+                }
+                println!(
+                    "{} already runs v{}, nothing to deploy",
+                    card_key, latest.version
+                );
+                return Ok(());
+            }
+
+            let (from_name, from_code) = latest
+                .map(|latest| (format!("v{}", latest.version), latest.code))
+                .unwrap_or_else(|| ("(nothing deployed)".to_string(), String::new()));
+            print!(
+                "{}",
+                card_code::diff(&from_code, &code, &from_name, "generated")
+            );
+
+            if !yes {
+                println!();
+                println!("Nothing has been deployed. Re-run with --yes to save the code.");
+                return Ok(());
+            }
+
+            let client = InvestecClient::new(config)?;
+            if let Some(secret) = &secret {
+                store_webhook_secret(&client, &card_key, secret).await?;
+            }
+            let saved = client.save_card_code(&card_key, &code).await?;
+            if publish {
+                client
+                    .publish_card_code(&card_key, &saved.code_id, &code)
+                    .await?;
+            }
+
+            let version = db::insert_card_code_deployment(
+                &database.pool,
+                &card_key,
+                &saved.code_id,
+                &code,
+                publish,
+            )
+            .await?;
+            println!(
+                "{} v{} to {} ({})",
+                if publish { "Published" } else { "Saved" },
+                version,
+                card_key,
+                saved.code_id
+            );
+        }
+        CardCommand::History { card_key } => {
+            let database = db::Database::initialize(&config.database).await?;
+            let deployments = db::list_card_code_deployments(&database.pool, &card_key).await?;
+            if deployments.is_empty() {
+                println!("Nothing deployed to {}", card_key);
+                return Ok(());
+            }
+
+            println!(
+                "{:>7}  {:<16} {:<10} Code id",
+                "Version", "Deployed", "Published"
+            );
+            for deployment in deployments {
+                println!(
+                    "{:>7}  {:<16} {:<10} {}",
+                    deployment.version,
+                    deployment.created_at.format("%Y-%m-%d %H:%M"),
+                    if deployment.published { "yes" } else { "no" },
+                    deployment.code_id
+                );
+            }
+        }
+        CardCommand::Show {
+            card_key,
+            published,
+        } => {
+            let client = InvestecClient::new(config)?;
+            let code = if published {
+                client.get_published_card_code(&card_key).await?
+            } else {
+                client.get_card_code(&card_key).await?
+            };
+            println!("// {}", code.code_id);
+            print!("{}", code.code);
+        }
+        CardCommand::Enable { card_key } => toggle(config, &card_key, true).await?,
+        CardCommand::Disable { card_key } => toggle(config, &card_key, false).await?,
+        CardCommand::Simulate {
+            card_key,
+            amount,
+            merchant_code,
+            merchant_name,
+            merchant_city,
+            country,
+            currency,
+        } => {
+            let database = db::Database::initialize(&config.database).await?;
+            let code =
+                card_code::generate(&database.pool, Utc::now().date_naive(), forwarding.as_ref())
+                    .await?;
+            let client = InvestecClient::new(config)?;
+            let simulation = Simulation {
+                code,
+                cents_amount: format!("{:.0}", (amount * 100.0).round()),
+                currency_code: currency,
+                merchant_code,
+                merchant_name,
+                merchant_city,
+                country_code: country,
+            };
+
+            for execution in client.execute_card_code(&card_key, &simulation).await? {
+                let outcome = match execution.authorization_approved {
+                    Some(true) => "approved",
+                    Some(false) => "declined",
+                    None => "-",
+                };
+                println!("{} {}", execution.type_, outcome);
+                for log in execution.logs {
+                    println!("  [{}] {}", log.level, log.content);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn toggle(config: Config, card_key: &str, enable: bool) -> anyhow::Result<()> {
+    let client = InvestecClient::new(config)?;
+    let enabled = client.toggle_programmable_feature(card_key, enable).await?;
+    println!(
+        "Card code is {} on {}",
+        if enabled { "enabled" } else { "disabled" },
+        card_key
+    );
+    Ok(())
+}
+
+/// Sets the webhook secret in the card's environment variables, keeping the
+/// others, so the generated code can send it without containing it.
+async fn store_webhook_secret(
+    client: &InvestecClient,
+    card_key: &str,
+    secret: &Secret,
+) -> anyhow::Result<()> {
+    let mut environment = client.get_card_environment(card_key).await?;
+    let current = environment
+        .variables
+        .get(card_code::WEBHOOK_SECRET_VARIABLE)
+        .and_then(|value| value.as_str());
+    if current == Some(secret.expose()) {
+        return Ok(());
+    }
+
+    environment.variables.insert(
+        card_code::WEBHOOK_SECRET_VARIABLE.to_string(),
+        secret.expose().into(),
+    );
+    client.save_card_environment(card_key, &environment).await?;
+    println!(
+        "Set {} in the environment variables of {}",
+        card_code::WEBHOOK_SECRET_VARIABLE,
+        card_key
+    );
+    Ok(())
+}
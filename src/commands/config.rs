@@ -0,0 +1,29 @@
+use clap::{Args, Subcommand};
+
+use crate::config::file::FileConfig;
+use crate::config::settings::Config;
+
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Validate the configuration and print the effective settings, secrets redacted
+    Check,
+}
+
+pub async fn run(config: Config, args: ConfigArgs) -> anyhow::Result<()> {
+    match args.command {
+        ConfigCommand::Check => {
+            print!(
+                "{}",
+                toml::to_string_pretty(&FileConfig::redacted(&config))?
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use clap::{Args, Subcommand};
+
+use crate::bucket_classifier::BucketClassifier;
+use crate::clients::InvestecClient;
+use crate::config::settings::Config;
+use crate::db;
+use crate::notifications::Notifier;
+use crate::scheduler::{self, JobContext, JobKind};
+use crate::shutdown::Shutdown;
+
+#[derive(Debug, Args)]
+pub struct JobsArgs {
+    #[command(subcommand)]
+    command: JobsCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum JobsCommand {
+    /// List scheduled jobs with their last and next run
+    List,
+    /// Run a job now, regardless of its schedule
+    Run {
+        #[arg(value_enum)]
+        job: JobKind,
+    },
+}
+
+pub async fn run(config: Config, args: JobsArgs) -> anyhow::Result<()> {
+    let database = db::Database::initialize(&config.database).await?;
+
+    match args.command {
+        JobsCommand::List => {
+            let jobs = db::list_scheduled_jobs(&database.pool).await?;
+            if jobs.is_empty() {
+                println!("No jobs scheduled yet, they are recorded when `run` starts");
+                return Ok(());
+            }
+
+            println!(
+                "{:<11} {:<16} {:<10} {:<17} {:>7} {:<17} {:>7}  Error",
+                "Job", "Schedule", "Status", "Last run", "Took", "Next run", "Skipped"
+            );
+            for job in jobs {
+                println!(
+                    "{:<11} {:<16} {:<10} {:<17} {:>7} {:<17} {:>7}  {}",
+                    job.name,
+                    job.schedule,
+                    job.status.as_deref().unwrap_or("-"),
+                    format_time(job.last_started_at),
+                    took(job.last_started_at, job.last_finished_at),
+                    format_time(job.next_run_at),
+                    job.skipped_runs,
+                    job.last_error.as_deref().unwrap_or("")
+                );
+            }
+        }
+        JobsCommand::Run { job } => {
+            let context = JobContext {
+                clients: Arc::new(InvestecClient::for_profiles(&config)?),
+                classifier: Arc::new(BucketClassifier::new(config.ollama.model.clone(), &config)),
+                notifier: Arc::new(Notifier::from_config(&config.notifications)?),
+                sync_config: config.sync.clone(),
+                reclassify_days: config.schedules.reclassify_days,
+                shutdown: Arc::new(Shutdown::new()),
+            };
+
+            scheduler::run_recorded(job, job.schedule(&config.schedules), &context, &database)
+                .await?;
+            println!("Job {} finished", job.as_str());
+        }
+    }
+
+    Ok(())
+}
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// How long the last run took; empty while it runs or before the first one.
+fn took(started: Option<DateTime<Utc>>, finished: Option<DateTime<Utc>>) -> String {
+    match (started, finished) {
+        (Some(started), Some(finished)) if finished >= started => {
+            let seconds = (finished - started).num_seconds();
+            if seconds < 60 {
+                format!("{}s", seconds)
+            } else {
+                format!("{}m{:02}s", seconds / 60, seconds % 60)
+            }
+        }
+        _ => "-".to_string(),
+    }
+}
@@ -0,0 +1,9 @@
+pub mod accounts;
+pub mod budget;
+pub mod card;
+pub mod config;
+pub mod jobs;
+pub mod payments;
+pub mod report;
+pub mod subscriptions;
+pub mod sweep;
@@ -0,0 +1,144 @@
+use clap::{Args, Subcommand};
+
+use crate::clients::InvestecClient;
+use crate::clients::investec::models::{PaymentInstruction, TransferInstruction, TransferResult};
+use crate::config::settings::Config;
+
+#[derive(Debug, Args)]
+pub struct PaymentsArgs {
+    #[command(subcommand)]
+    command: PaymentsCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum PaymentsCommand {
+    /// List saved beneficiaries and beneficiary categories
+    Beneficiaries,
+    /// Transfer money between your own accounts
+    Transfer {
+        /// Account id to transfer from
+        #[arg(long)]
+        from: String,
+        /// Account id to transfer to
+        #[arg(long)]
+        to: String,
+        amount: f64,
+        #[arg(long)]
+        reference: String,
+        /// Execute the transfer instead of printing what would happen
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Pay a saved beneficiary
+    Pay {
+        /// Account id to pay from
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        beneficiary: String,
+        amount: f64,
+        #[arg(long)]
+        reference: String,
+        /// Reference shown on the beneficiary's statement, defaults to --reference
+        #[arg(long)]
+        their_reference: Option<String>,
+        /// Execute the payment instead of printing what would happen
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+pub async fn run(config: Config, args: PaymentsArgs) -> anyhow::Result<()> {
+    let client = InvestecClient::new(config)?;
+
+    match args.command {
+        PaymentsCommand::Beneficiaries => {
+            let categories = client.get_beneficiary_categories().await?;
+            let beneficiaries = client.get_beneficiaries().await?;
+
+            println!(
+                "{:<36} {:<30} {:<16} Category",
+                "Beneficiary id", "Name", "Bank"
+            );
+            for beneficiary in beneficiaries {
+                let category = categories
+                    .iter()
+                    .find(|category| beneficiary.category_id.as_deref() == Some(&category.id))
+                    .map(|category| category.name.as_str())
+                    .unwrap_or("-");
+                println!(
+                    "{:<36} {:<30} {:<16} {}",
+                    beneficiary.beneficiary_id,
+                    beneficiary.beneficiary_name,
+                    beneficiary.bank.as_deref().unwrap_or("-"),
+                    category
+                );
+            }
+        }
+        PaymentsCommand::Transfer {
+            from,
+            to,
+            amount,
+            reference,
+            yes,
+        } => {
+            ensure_positive(amount)?;
+            if !yes {
+                println!(
+                    "Would transfer {:.2} from {} to {} ({}). Re-run with --yes to execute.",
+                    amount, from, to, reference
+                );
+                return Ok(());
+            }
+
+            let transfer = TransferInstruction::new(&to, amount, &reference, &reference);
+            print_results(&client.transfer(&from, &[transfer]).await?);
+        }
+        PaymentsCommand::Pay {
+            from,
+            beneficiary,
+            amount,
+            reference,
+            their_reference,
+            yes,
+        } => {
+            ensure_positive(amount)?;
+            let their_reference = their_reference.unwrap_or_else(|| reference.clone());
+            if !yes {
+                println!(
+                    "Would pay {:.2} from {} to beneficiary {} ({}). Re-run with --yes to execute.",
+                    amount, from, beneficiary, reference
+                );
+                return Ok(());
+            }
+
+            let payment =
+                PaymentInstruction::new(&beneficiary, amount, &reference, &their_reference);
+            print_results(&client.pay_beneficiaries(&from, &[payment]).await?);
+        }
+    }
+
+    Ok(())
+}
+
+fn ensure_positive(amount: f64) -> anyhow::Result<()> {
+    if !(amount.is_finite() && amount > 0.0) {
+        anyhow::bail!("Amount must be a number greater than zero");
+    }
+    Ok(())
+}
+
+fn print_results(results: &[TransferResult]) {
+    for result in results {
+        println!(
+            "{} {}{}",
+            result.payment_reference_number,
+            result.status,
+            if result.authorisation_required {
+                " (authorisation required)"
+            } else {
+                ""
+            }
+        );
+    }
+}
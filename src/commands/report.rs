@@ -0,0 +1,49 @@
+use chrono::Utc;
+use clap::Args;
+
+use crate::config::settings::Config;
+use crate::db;
+use crate::reports::{self, ReportFormat};
+
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    /// First month to report on (YYYY-MM), defaults to the current month
+    #[arg(long)]
+    from: Option<String>,
+    /// Last month to report on (YYYY-MM), defaults to the current month
+    #[arg(long)]
+    to: Option<String>,
+    /// Only include transactions for this account id
+    #[arg(long)]
+    account: Option<String>,
+    /// Only include transactions synced from this Investec profile; all
+    /// profiles are merged by default
+    #[arg(long)]
+    profile: Option<String>,
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    format: ReportFormat,
+    /// Number of top merchants to list per bucket
+    #[arg(long, default_value_t = 3)]
+    top: usize,
+}
+
+pub async fn run(config: Config, args: ReportArgs) -> anyhow::Result<()> {
+    let current_month = Utc::now().format("%Y-%m").to_string();
+    let from_month = args.from.unwrap_or_else(|| current_month.clone());
+    let to_month = args.to.unwrap_or(current_month);
+
+    let database = db::Database::initialize(&config.database).await?;
+    let report = reports::generate_monthly_report(
+        &database.pool,
+        &from_month,
+        &to_month,
+        args.account.as_deref(),
+        args.profile.as_deref(),
+        args.top,
+    )
+    .await?;
+
+    print!("{}", reports::render::render(&report, args.format)?);
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use chrono::Utc;
+use clap::Args;
+
+use crate::config::settings::Config;
+use crate::db;
+use crate::recurring;
+
+#[derive(Debug, Args)]
+pub struct SubscriptionsArgs {
+    /// Re-run detection against stored transactions before listing
+    #[arg(long)]
+    detect: bool,
+}
+
+pub async fn run(config: Config, args: SubscriptionsArgs) -> anyhow::Result<()> {
+    let database = db::Database::initialize(&config.database).await?;
+
+    if args.detect {
+        let flags =
+            recurring::evaluate_subscriptions(&database.pool, Utc::now().date_naive()).await?;
+        for flag in flags {
+            println!("{}: {}", flag.kind.as_str(), flag.description);
+        }
+    }
+
+    let subscriptions = db::list_subscriptions(&database.pool).await?;
+    if subscriptions.is_empty() {
+        println!("No subscriptions detected");
+        return Ok(());
+    }
+
+    println!(
+        "{:<32} {:<8} {:>10} {:>5} {:<12} {:<12} Status",
+        "Description", "Cadence", "Amount", "Seen", "Last seen", "Next due"
+    );
+    for subscription in subscriptions {
+        println!(
+            "{:<32} {:<8} {:>10.2} {:>5} {:<12} {:<12} {}",
+            subscription.description,
+            subscription.cadence,
+            subscription.expected_amount,
+            subscription.occurrences,
+            subscription.last_seen_date.to_string(),
+            subscription.next_expected_date.to_string(),
+            subscription.status
+        );
+    }
+
+    Ok(())
+}
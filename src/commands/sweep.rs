@@ -0,0 +1,238 @@
+use chrono::{Months, Utc};
+use clap::{Args, Subcommand};
+
+use super::budget::known_bucket;
+use crate::clients::InvestecClient;
+use crate::config::settings::Config;
+use crate::db;
+use crate::reports::monthly::parse_month;
+use crate::sweeps::{self, SweepKind, SweepPlan};
+
+#[derive(Debug, Args)]
+pub struct SweepArgs {
+    #[command(subcommand)]
+    command: SweepCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum SweepCommand {
+    /// Manage the rules that decide what gets swept
+    Rule {
+        #[command(subcommand)]
+        command: RuleCommand,
+    },
+    /// Compute and store the plan for a month without transferring anything
+    Plan {
+        /// Month to settle as YYYY-MM, defaults to last month
+        #[arg(long)]
+        month: Option<String>,
+    },
+    /// Show stored plans and their transfers
+    List,
+    /// Execute the transfers of a plan
+    Approve { plan_id: i32 },
+    /// Record whether a transfer with an unknown outcome went through, after
+    /// checking the account
+    Confirm {
+        item_id: i32,
+        #[arg(value_enum)]
+        outcome: TransferOutcome,
+    },
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum TransferOutcome {
+    /// The money moved; the transfer won't be sent again
+    Executed,
+    /// It didn't; the next approval sends it again
+    Failed,
+}
+
+#[derive(Debug, Subcommand)]
+enum RuleCommand {
+    /// Add a rule sweeping from one account to another
+    Add {
+        #[arg(value_enum)]
+        kind: SweepKind,
+        #[arg(long)]
+        bucket: String,
+        /// Account id to sweep from
+        #[arg(long)]
+        from: String,
+        /// Account id to sweep into
+        #[arg(long)]
+        to: String,
+        /// Round-up increment, e.g. 10 to round every purchase up to R10
+        #[arg(long)]
+        round_to: Option<f64>,
+    },
+    /// List sweep rules
+    List,
+    /// Delete a sweep rule
+    Remove { id: i32 },
+}
+
+pub async fn run(config: Config, args: SweepArgs) -> anyhow::Result<()> {
+    let database = db::Database::initialize(&config.database).await?;
+
+    match args.command {
+        SweepCommand::Rule { command } => match command {
+            RuleCommand::Add {
+                kind,
+                bucket,
+                from,
+                to,
+                round_to,
+            } => {
+                let bucket = known_bucket(&config, &bucket)?;
+                match (kind, round_to) {
+                    (SweepKind::RoundUp, None) => {
+                        anyhow::bail!("--round-to is required for round-up rules")
+                    }
+                    (SweepKind::RoundUp, Some(round_to)) if round_to <= 0.0 => {
+                        anyhow::bail!("--round-to must be greater than zero")
+                    }
+                    (SweepKind::Underspend, Some(_)) => {
+                        anyhow::bail!("--round-to only applies to round-up rules")
+                    }
+                    _ => {}
+                }
+                if from == to {
+                    anyhow::bail!("--from and --to must be different accounts");
+                }
+
+                let id = db::insert_sweep_rule(
+                    &database.pool,
+                    kind.as_str(),
+                    &bucket,
+                    &from,
+                    &to,
+                    round_to,
+                )
+                .await?;
+                println!("Added {} sweep rule {} for {}", kind, id, bucket);
+            }
+            RuleCommand::List => {
+                let rules = db::list_sweep_rules(&database.pool).await?;
+                if rules.is_empty() {
+                    println!("No sweep rules defined");
+                    return Ok(());
+                }
+
+                println!(
+                    "{:>4}  {:<11} {:<20} {:<36} {:<36} {:>8}",
+                    "Id", "Kind", "Bucket", "From", "To", "Round to"
+                );
+                for rule in rules {
+                    println!(
+                        "{:>4}  {:<11} {:<20} {:<36} {:<36} {:>8}",
+                        rule.id,
+                        rule.kind,
+                        rule.bucket,
+                        rule.from_account_id,
+                        rule.to_account_id,
+                        rule.round_to
+                            .map(|round_to| format!("{:.2}", round_to))
+                            .unwrap_or_else(|| "-".to_string())
+                    );
+                }
+            }
+            RuleCommand::Remove { id } => {
+                if db::delete_sweep_rule(&database.pool, id).await? {
+                    println!("Removed sweep rule {}", id);
+                } else {
+                    println!("No sweep rule {}", id);
+                }
+            }
+        },
+        SweepCommand::Plan { month } => {
+            let plan = match month {
+                Some(month) => {
+                    let start = parse_month(&month)?;
+                    sweeps::plan_sweeps_for(&database.pool, start, start + Months::new(1)).await?
+                }
+                None => sweeps::plan_sweeps(&database.pool, Utc::now().date_naive()).await?,
+            };
+
+            match plan {
+                Some(plan) => {
+                    print_plan(&database.pool, &plan).await?;
+                    println!();
+                    println!(
+                        "Nothing has been transferred. Run `sweep approve {}` to execute.",
+                        plan.id
+                    );
+                }
+                None => println!("Nothing to sweep, or the month already has a plan"),
+            }
+        }
+        SweepCommand::List => {
+            let plans = db::list_sweep_plans(&database.pool).await?;
+            if plans.is_empty() {
+                println!("No sweep plans");
+                return Ok(());
+            }
+
+            for plan in plans {
+                print_plan(&database.pool, &plan).await?;
+                println!();
+            }
+        }
+        SweepCommand::Approve { plan_id } => {
+            let client = InvestecClient::new(config)?;
+            let items = sweeps::approve_plan(&database.pool, &client, plan_id).await?;
+            for item in items {
+                println!(
+                    "{:>4}  {:<9} {:>10.2} {} -> {} {}",
+                    item.id,
+                    item.status,
+                    item.amount,
+                    item.from_account_id,
+                    item.to_account_id,
+                    item.payment_reference.or(item.error).unwrap_or_default()
+                );
+            }
+        }
+        SweepCommand::Confirm { item_id, outcome } => {
+            let executed = matches!(outcome, TransferOutcome::Executed);
+            sweeps::confirm_item(&database.pool, item_id, executed).await?;
+            let status = if executed {
+                sweeps::STATUS_EXECUTED
+            } else {
+                sweeps::STATUS_FAILED
+            };
+            println!("Recorded sweep transfer {} as {}", item_id, status);
+        }
+    }
+
+    Ok(())
+}
+
+async fn print_plan(pool: &sqlx::PgPool, plan: &SweepPlan) -> anyhow::Result<()> {
+    let approved = plan
+        .approved_at
+        .map(|at| format!(", approved {}", at.format("%Y-%m-%d %H:%M")))
+        .unwrap_or_default();
+    println!(
+        "Plan {} for {} ({}, created {}{})",
+        plan.id,
+        plan.period_start.format("%Y-%m"),
+        plan.status,
+        plan.created_at.format("%Y-%m-%d %H:%M"),
+        approved
+    );
+
+    for item in db::list_sweep_plan_items(pool, plan.id).await? {
+        println!(
+            "  {:>4}  {:<9} {:>10.2} {} -> {}  {}",
+            item.id,
+            item.status,
+            item.amount,
+            item.from_account_id,
+            item.to_account_id,
+            item.reason
+        );
+    }
+
+    Ok(())
+}
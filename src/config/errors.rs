@@ -1,7 +1,82 @@
+use std::fmt;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
-    #[error("Missing required environment variable: {0}")]
-    MissingRequiredVar(String),
+    #[error("Missing required setting {0}")]
+    MissingVar(String),
+
+    #[error("{key} must be {expected}, got {value:?}")]
+    InvalidValue {
+        key: String,
+        value: String,
+        expected: &'static str,
+    },
+
+    #[error("{key} is not a valid URL ({reason}): {value:?}")]
+    InvalidUrl {
+        key: String,
+        value: String,
+        reason: String,
+    },
+
+    #[error("{0} and {1} must be set together, or neither")]
+    Unpaired(&'static str, &'static str),
+
+    #[error("{0} requires {1}")]
+    Requires(&'static str, &'static str),
+
+    #[error("No classification provider: set GEMINI_API_KEY and GEMINI_MODEL, or OLLAMA_MODEL")]
+    NoClassifier,
+
+    #[error("BUCKETS contains an empty bucket name")]
+    EmptyBucketName,
+
+    #[error("BUCKETS contains {0:?} more than once")]
+    DuplicateBucket(String),
+
+    #[error("BUCKETS must include the fallback bucket \"Other\"")]
+    MissingOtherBucket,
+
+    #[error("Failed to read config file {path}: {message}")]
+    File { path: String, message: String },
+
+    #[error("Failed to read {key} {path}: {message}")]
+    SecretFile {
+        key: String,
+        path: String,
+        message: String,
+    },
+
+    #[error("Invalid or duplicate Investec profile name: {0}")]
+    InvalidProfileName(String),
+
+    #[error("Unknown Investec profile: {0}")]
+    UnknownProfile(String),
+}
+
+/// Every problem found while loading the config, so they can be fixed in one go.
+#[derive(Debug, Error)]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_slice() {
+            [error] => write!(f, "Invalid configuration: {}", error),
+            errors => {
+                write!(f, "Invalid configuration ({} problems):", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  - {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<ConfigError> for ConfigErrors {
+    fn from(error: ConfigError) -> Self {
+        Self(vec![error])
+    }
 }
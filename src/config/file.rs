@@ -0,0 +1,608 @@
+//! The optional TOML or YAML config file. Every setting maps onto the
+//! environment variable of the same meaning, and environment variables win
+//! over the file.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::errors::ConfigError;
+use super::secret::Secret;
+use super::settings::{Config, DEFAULT_PROFILE, SCHEDULE_OFF};
+
+/// Shown in place of secrets by `config check`.
+pub const REDACTED: &str = "***";
+
+/// A file setting, keyed by its environment variable name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileValue {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub city: Option<String>,
+    pub investec: InvestecSection,
+    pub database: DatabaseSection,
+    pub providers: ProvidersSection,
+    pub buckets: BucketsSection,
+    pub schedules: SchedulesSection,
+    pub sync: SyncSection,
+    pub notifications: NotificationsSection,
+    pub card_webhook: CardWebhookSection,
+    pub metrics: MetricsSection,
+    pub health: HealthSection,
+    pub telemetry: TelemetrySection,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InvestecSection {
+    pub base_url: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<Secret>,
+    pub x_api_key: Option<Secret>,
+    pub retry_max_attempts: Option<u32>,
+    pub retry_base_delay_ms: Option<u64>,
+    pub retry_max_delay_ms: Option<u64>,
+    pub circuit_failure_threshold: Option<u32>,
+    pub circuit_cooldown_secs: Option<u64>,
+    pub enable_payments: Option<bool>,
+    pub enable_cards: Option<bool>,
+    /// Named credentials, used instead of the ones above when present.
+    pub profiles: Vec<ProfileSection>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileSection {
+    pub name: String,
+    pub base_url: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<Secret>,
+    pub x_api_key: Option<Secret>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSection {
+    pub url: Option<String>,
+    pub max_connections: Option<u32>,
+    pub min_connections: Option<u32>,
+    pub acquire_timeout_secs: Option<u64>,
+    pub auto_migrate: Option<bool>,
+}
+
+impl fmt::Debug for DatabaseSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatabaseSection")
+            .field("url", &self.url.as_deref().map(redact_password))
+            .field("max_connections", &self.max_connections)
+            .field("min_connections", &self.min_connections)
+            .field("acquire_timeout_secs", &self.acquire_timeout_secs)
+            .field("auto_migrate", &self.auto_migrate)
+            .finish()
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProvidersSection {
+    pub gemini: GeminiSection,
+    pub ollama: OllamaSection,
+    pub google_search: GoogleSearchSection,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeminiSection {
+    pub api_key: Option<Secret>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OllamaSection {
+    pub model: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GoogleSearchSection {
+    pub api_key: Option<Secret>,
+    pub engine_id: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BucketsSection {
+    pub categories: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulesSection {
+    pub sync: Option<String>,
+    pub balances: Option<String>,
+    pub recurring: Option<String>,
+    pub reports: Option<String>,
+    pub reclassify: Option<String>,
+    pub sweeps: Option<String>,
+    pub jitter_secs: Option<u64>,
+    pub reclassify_days: Option<u32>,
+    pub shutdown_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncSection {
+    pub transaction_types: Vec<String>,
+    pub exclude_transaction_types: Vec<String>,
+    pub include_pending: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationsSection {
+    pub webhook_url: Option<String>,
+    pub webhook_template: Option<String>,
+    pub webhook_events: Vec<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<Secret>,
+    pub smtp_security: Option<String>,
+    pub email_from: Option<String>,
+    pub email_to: Vec<String>,
+    pub email_subject: Option<String>,
+    pub email_body: Option<String>,
+    pub email_events: Vec<String>,
+    pub command: Option<String>,
+    pub command_events: Vec<String>,
+    pub retry_attempts: Option<u32>,
+    pub retry_delay_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CardWebhookSection {
+    pub listen: Option<String>,
+    pub secret: Option<Secret>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSection {
+    pub listen: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthSection {
+    pub listen: Option<String>,
+    pub max_sync_age_hours: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetrySection {
+    pub log_format: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub service_name: Option<String>,
+}
+
+/// Collects the settings present in the file under their variable names.
+#[derive(Default)]
+struct Vars(HashMap<String, FileValue>);
+
+impl Vars {
+    fn scalar(&mut self, key: &str, value: Option<impl ToString>) {
+        if let Some(value) = value {
+            self.0
+                .insert(key.to_string(), FileValue::Scalar(value.to_string()));
+        }
+    }
+
+    fn secret(&mut self, key: &str, value: Option<Secret>) {
+        self.scalar(key, value.as_ref().map(Secret::expose));
+    }
+
+    fn list(&mut self, key: &str, values: Vec<String>) {
+        if !values.is_empty() {
+            self.0.insert(key.to_string(), FileValue::List(values));
+        }
+    }
+}
+
+impl FileConfig {
+    /// Parses `path` as YAML when it ends in `.yaml` or `.yml`, otherwise as TOML.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let file_error = |message: String| ConfigError::File {
+            path: path.display().to_string(),
+            message,
+        };
+        let contents = std::fs::read_to_string(path).map_err(|e| file_error(e.to_string()))?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => {
+                serde_yaml::from_str(&contents).map_err(|e| file_error(e.to_string()))
+            }
+            _ => toml::from_str(&contents).map_err(|e| file_error(e.to_string())),
+        }
+    }
+
+    pub fn into_vars(self) -> HashMap<String, FileValue> {
+        let mut vars = Vars::default();
+
+        let investec = self.investec;
+        vars.scalar("INVESTEC_BASE_URL", investec.base_url);
+        vars.scalar("INVESTEC_CLIENT_ID", investec.client_id);
+        vars.secret("INVESTEC_CLIENT_SECRET", investec.client_secret);
+        vars.secret("INVESTEC_X_API_KEY", investec.x_api_key);
+        vars.scalar("INVESTEC_RETRY_MAX_ATTEMPTS", investec.retry_max_attempts);
+        vars.scalar("INVESTEC_RETRY_BASE_DELAY_MS", investec.retry_base_delay_ms);
+        vars.scalar("INVESTEC_RETRY_MAX_DELAY_MS", investec.retry_max_delay_ms);
+        vars.scalar(
+            "INVESTEC_CIRCUIT_FAILURE_THRESHOLD",
+            investec.circuit_failure_threshold,
+        );
+        vars.scalar(
+            "INVESTEC_CIRCUIT_COOLDOWN_SECS",
+            investec.circuit_cooldown_secs,
+        );
+        vars.scalar("INVESTEC_ENABLE_PAYMENTS", investec.enable_payments);
+        vars.scalar("INVESTEC_ENABLE_CARDS", investec.enable_cards);
+        vars.list(
+            "INVESTEC_PROFILES",
+            investec
+                .profiles
+                .iter()
+                .map(|profile| profile.name.clone())
+                .collect(),
+        );
+        for profile in investec.profiles {
+            let prefix = format!("INVESTEC_{}", profile.name.to_uppercase().replace('-', "_"));
+            vars.scalar(&format!("{}_BASE_URL", prefix), profile.base_url);
+            vars.scalar(&format!("{}_CLIENT_ID", prefix), profile.client_id);
+            vars.secret(&format!("{}_CLIENT_SECRET", prefix), profile.client_secret);
+            vars.secret(&format!("{}_X_API_KEY", prefix), profile.x_api_key);
+        }
+
+        let database = self.database;
+        vars.scalar("DATABASE_URL", database.url);
+        vars.scalar("DATABASE_MAX_CONNECTIONS", database.max_connections);
+        vars.scalar("DATABASE_MIN_CONNECTIONS", database.min_connections);
+        vars.scalar(
+            "DATABASE_ACQUIRE_TIMEOUT_SECS",
+            database.acquire_timeout_secs,
+        );
+        vars.scalar("DATABASE_AUTO_MIGRATE", database.auto_migrate);
+
+        let providers = self.providers;
+        vars.secret("GEMINI_API_KEY", providers.gemini.api_key);
+        vars.scalar("GEMINI_MODEL", providers.gemini.model);
+        vars.scalar("OLLAMA_MODEL", providers.ollama.model);
+        vars.scalar("OLLAMA_HOST", providers.ollama.host);
+        vars.scalar("OLLAMA_PORT", providers.ollama.port);
+        vars.secret("GOOGLE_SEARCH_API_KEY", providers.google_search.api_key);
+        vars.scalar("GOOGLE_SEARCH_ENGINE_ID", providers.google_search.engine_id);
+
+        vars.list("BUCKETS", self.buckets.categories);
+        let schedules = self.schedules;
+        vars.scalar("SCHEDULE_SYNC", schedules.sync);
+        vars.scalar("SCHEDULE_BALANCES", schedules.balances);
+        vars.scalar("SCHEDULE_RECURRING", schedules.recurring);
+        vars.scalar("SCHEDULE_REPORTS", schedules.reports);
+        vars.scalar("SCHEDULE_RECLASSIFY", schedules.reclassify);
+        vars.scalar("SWEEP_SCHEDULE", schedules.sweeps);
+        vars.scalar("SCHEDULE_JITTER_SECS", schedules.jitter_secs);
+        vars.scalar("RECLASSIFY_DAYS", schedules.reclassify_days);
+        vars.scalar("SHUTDOWN_TIMEOUT_SECS", schedules.shutdown_timeout_secs);
+        vars.list("SYNC_TRANSACTION_TYPES", self.sync.transaction_types);
+        vars.list(
+            "SYNC_EXCLUDE_TRANSACTION_TYPES",
+            self.sync.exclude_transaction_types,
+        );
+        vars.scalar("SYNC_INCLUDE_PENDING", self.sync.include_pending);
+
+        let notifications = self.notifications;
+        vars.scalar("NOTIFY_WEBHOOK_URL", notifications.webhook_url);
+        vars.scalar("NOTIFY_WEBHOOK_TEMPLATE", notifications.webhook_template);
+        vars.list("NOTIFY_WEBHOOK_EVENTS", notifications.webhook_events);
+        vars.scalar("NOTIFY_SMTP_HOST", notifications.smtp_host);
+        vars.scalar("NOTIFY_SMTP_PORT", notifications.smtp_port);
+        vars.scalar("NOTIFY_SMTP_USERNAME", notifications.smtp_username);
+        vars.secret("NOTIFY_SMTP_PASSWORD", notifications.smtp_password);
+        vars.scalar("NOTIFY_SMTP_SECURITY", notifications.smtp_security);
+        vars.scalar("NOTIFY_EMAIL_FROM", notifications.email_from);
+        vars.list("NOTIFY_EMAIL_TO", notifications.email_to);
+        vars.scalar("NOTIFY_EMAIL_SUBJECT", notifications.email_subject);
+        vars.scalar("NOTIFY_EMAIL_BODY", notifications.email_body);
+        vars.list("NOTIFY_EMAIL_EVENTS", notifications.email_events);
+        vars.scalar("NOTIFY_COMMAND", notifications.command);
+        vars.list("NOTIFY_COMMAND_EVENTS", notifications.command_events);
+        vars.scalar("NOTIFY_RETRY_ATTEMPTS", notifications.retry_attempts);
+        vars.scalar("NOTIFY_RETRY_DELAY_MS", notifications.retry_delay_ms);
+
+        vars.scalar("CARD_WEBHOOK_LISTEN", self.card_webhook.listen);
+        vars.secret("CARD_WEBHOOK_SECRET", self.card_webhook.secret);
+        vars.scalar("CARD_WEBHOOK_URL", self.card_webhook.url);
+        vars.scalar("METRICS_LISTEN", self.metrics.listen);
+        vars.scalar("HEALTH_LISTEN", self.health.listen);
+        vars.scalar("READY_MAX_SYNC_AGE_HOURS", self.health.max_sync_age_hours);
+        vars.scalar("LOG_FORMAT", self.telemetry.log_format);
+        vars.scalar("OTEL_EXPORTER_OTLP_ENDPOINT", self.telemetry.otlp_endpoint);
+        vars.scalar("OTEL_SERVICE_NAME", self.telemetry.service_name);
+
+        vars.scalar("CITY", self.city);
+
+        vars.0
+    }
+
+    /// The effective `config` in file form with credentials, passwords and
+    /// secret URL parts replaced by [`REDACTED`].
+    pub fn redacted(config: &Config) -> Self {
+        let secret = |value: &Option<Secret>| value.as_ref().map(|_| Secret::from(REDACTED));
+        let investec = &config.investec;
+        let named_profiles = config
+            .investec_profiles
+            .iter()
+            .any(|profile| profile.profile != DEFAULT_PROFILE);
+        let notifications = &config.notifications;
+
+        Self {
+            city: config.city.clone(),
+            investec: InvestecSection {
+                base_url: Some(investec.base_url.clone()),
+                client_id: (!named_profiles).then(|| investec.client_id.clone()),
+                client_secret: (!named_profiles).then(|| Secret::from(REDACTED)),
+                x_api_key: (!named_profiles).then(|| Secret::from(REDACTED)),
+                retry_max_attempts: Some(investec.retry_max_attempts),
+                retry_base_delay_ms: Some(investec.retry_base_delay_ms),
+                retry_max_delay_ms: Some(investec.retry_max_delay_ms),
+                circuit_failure_threshold: Some(investec.circuit_failure_threshold),
+                circuit_cooldown_secs: Some(investec.circuit_cooldown_secs),
+                enable_payments: Some(investec.payments_enabled),
+                enable_cards: Some(investec.cards_enabled),
+                profiles: if named_profiles {
+                    config
+                        .investec_profiles
+                        .iter()
+                        .map(|profile| ProfileSection {
+                            name: profile.profile.clone(),
+                            base_url: Some(profile.base_url.clone()),
+                            client_id: Some(profile.client_id.clone()),
+                            client_secret: Some(Secret::from(REDACTED)),
+                            x_api_key: Some(Secret::from(REDACTED)),
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                },
+            },
+            database: DatabaseSection {
+                url: Some(redact_password(&config.database.url)),
+                max_connections: Some(config.database.max_connections),
+                min_connections: Some(config.database.min_connections),
+                acquire_timeout_secs: Some(config.database.acquire_timeout_secs),
+                auto_migrate: Some(config.database.auto_migrate),
+            },
+            providers: ProvidersSection {
+                gemini: GeminiSection {
+                    api_key: secret(&config.gemini.api_key),
+                    model: config.gemini.model.clone(),
+                },
+                ollama: OllamaSection {
+                    model: config.ollama.model.clone(),
+                    host: config.ollama.host.clone(),
+                    port: config.ollama.port,
+                },
+                google_search: GoogleSearchSection {
+                    api_key: secret(&config.google_search.api_key),
+                    engine_id: config.google_search.engine_id.clone(),
+                },
+            },
+            buckets: BucketsSection {
+                categories: config.buckets.categories.clone(),
+            },
+            schedules: {
+                let schedules = &config.schedules;
+                let schedule = |schedule: &Option<String>| {
+                    Some(schedule.clone().unwrap_or_else(|| SCHEDULE_OFF.to_string()))
+                };
+                SchedulesSection {
+                    sync: schedule(&schedules.sync),
+                    balances: schedule(&schedules.balances),
+                    recurring: schedule(&schedules.recurring),
+                    reports: schedule(&schedules.reports),
+                    reclassify: schedule(&schedules.reclassify),
+                    sweeps: schedule(&schedules.sweeps),
+                    jitter_secs: Some(schedules.jitter_secs),
+                    reclassify_days: Some(schedules.reclassify_days),
+                    shutdown_timeout_secs: Some(schedules.shutdown_timeout_secs),
+                }
+            },
+            sync: SyncSection {
+                transaction_types: config.sync.transaction_types.clone(),
+                exclude_transaction_types: config.sync.exclude_transaction_types.clone(),
+                include_pending: Some(config.sync.include_pending),
+            },
+            notifications: NotificationsSection {
+                webhook_url: notifications.webhook_url.as_deref().map(redact_url),
+                webhook_template: notifications.webhook_template.clone(),
+                webhook_events: notifications.webhook_events.clone(),
+                smtp_host: notifications.smtp_host.clone(),
+                smtp_port: notifications.smtp_port,
+                smtp_username: notifications.smtp_username.clone(),
+                smtp_password: secret(&notifications.smtp_password),
+                smtp_security: notifications.smtp_security.clone(),
+                email_from: notifications.email_from.clone(),
+                email_to: notifications.email_to.clone(),
+                email_subject: notifications.email_subject_template.clone(),
+                email_body: notifications.email_body_template.clone(),
+                email_events: notifications.email_events.clone(),
+                command: notifications.command.clone(),
+                command_events: notifications.command_events.clone(),
+                retry_attempts: Some(notifications.retry_attempts),
+                retry_delay_ms: Some(notifications.retry_delay_ms),
+            },
+            card_webhook: CardWebhookSection {
+                listen: config.card_webhook.listen.clone(),
+                secret: secret(&config.card_webhook.secret),
+                url: config.card_webhook.url.clone(),
+            },
+            metrics: MetricsSection {
+                listen: config.metrics.listen.clone(),
+            },
+            health: HealthSection {
+                listen: config.health.listen.clone(),
+                max_sync_age_hours: Some(config.health.max_sync_age_hours),
+            },
+            telemetry: TelemetrySection {
+                log_format: Some(config.telemetry.log_format.as_str().to_string()),
+                otlp_endpoint: config.telemetry.otlp_endpoint.clone(),
+                service_name: Some(config.telemetry.service_name.clone()),
+            },
+        }
+    }
+}
+
+/// Masks the password of a database URL, keeping the user, host and database.
+pub fn redact_password(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some(REDACTED));
+            parsed.to_string()
+        }
+        Ok(_) => url.to_string(),
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+/// Keeps the scheme, host and port of a URL; webhook URLs usually carry
+/// their token in the path or query.
+pub fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(parsed) if parsed.has_host() => {
+            let port = parsed
+                .port()
+                .map(|port| format!(":{}", port))
+                .unwrap_or_default();
+            format!(
+                "{}://{}{}/{}",
+                parsed.scheme(),
+                parsed.host_str().unwrap_or_default(),
+                port,
+                REDACTED
+            )
+        }
+        _ => REDACTED.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_maps_onto_variable_names() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            city = "Cape Town"
+
+            [investec]
+            enable_payments = true
+
+            [[investec.profiles]]
+            name = "business"
+            client_id = "biz"
+
+            [database]
+            url = "postgres://app:pw@db/itb"
+
+            [buckets]
+            categories = ["Food", "Bills, Utilities"]
+            "#,
+        )
+        .unwrap();
+        let vars = file.into_vars();
+
+        let scalar = |value: &str| Some(FileValue::Scalar(value.to_string()));
+        assert_eq!(vars.get("CITY").cloned(), scalar("Cape Town"));
+        assert_eq!(
+            vars.get("INVESTEC_ENABLE_PAYMENTS").cloned(),
+            scalar("true")
+        );
+        assert_eq!(
+            vars.get("INVESTEC_BUSINESS_CLIENT_ID").cloned(),
+            scalar("biz")
+        );
+        assert_eq!(
+            vars.get("BUCKETS").cloned(),
+            Some(FileValue::List(vec![
+                "Food".to_string(),
+                "Bills, Utilities".to_string()
+            ]))
+        );
+        assert!(!vars.contains_key("GEMINI_API_KEY"));
+
+        assert!(toml::from_str::<FileConfig>("[investec]\nclient_idd = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_debug_hides_secrets() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            [investec]
+            client_secret = "s-investec"
+
+            [[investec.profiles]]
+            name = "business"
+            x_api_key = "s-profile"
+
+            [database]
+            url = "postgres://app:s-database@db/itb"
+
+            [providers.gemini]
+            api_key = "s-gemini"
+
+            [notifications]
+            smtp_password = "s-smtp"
+
+            [card_webhook]
+            secret = "s-card"
+            "#,
+        )
+        .unwrap();
+
+        assert!(!format!("{:?}", file).contains("s-"));
+        let vars = file.into_vars();
+        assert_eq!(
+            vars.get("CARD_WEBHOOK_SECRET").cloned(),
+            Some(FileValue::Scalar("s-card".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_redacted_hides_secrets() {
+        let mut config = Config::for_tests();
+        config.database.url = "postgres://app:hunter2@db:5432/itb".to_string();
+        config.notifications.webhook_url =
+            Some("https://hooks.slack.com/services/T0/B0/token".to_string());
+
+        let rendered = toml::to_string(&FileConfig::redacted(&config)).unwrap();
+        assert!(!rendered.contains("hunter2"));
+        assert!(!rendered.contains("token"));
+        assert!(rendered.contains("postgres://app:***@db:5432/itb"));
+        assert!(rendered.contains("https://hooks.slack.com/***"));
+        assert!(rendered.contains(r#"client_secret = "***""#));
+    }
+}
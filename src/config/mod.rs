@@ -1,2 +1,4 @@
 pub mod errors;
+pub mod file;
+pub mod secret;
 pub mod settings;
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::file::REDACTED;
+
+/// A credential that is redacted in `Debug` output, so logging a config or
+/// client can't leak it. Use [`Secret::expose`] where the value is sent.
+/// Serializes as the plain value, so only serialize redacted copies.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
@@ -1,24 +1,130 @@
 use anyhow::Result;
 use sqlx::migrate::Migrator;
 use sqlx::{
-    Row,
-    postgres::{PgConnectOptions, PgPool},
+    Postgres, Row,
+    pool::PoolConnection,
+    postgres::{PgConnectOptions, PgPool, PgPoolOptions},
 };
 use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::settings::DatabaseConfig;
+
+/// Best available date of a stored transaction as `YYYY-MM-DD` text.
+const TRANSACTION_DATE_SQL: &str = "COALESCE(t.transaction_date, t.posting_date, t.value_date, \
+     TO_CHAR(t.created_at, 'YYYY-MM-DD'))";
 
 pub struct Database {
     pub pool: PgPool,
 }
 
 impl Database {
-    pub async fn initialize(database_url: &str) -> Result<Self> {
-        let options = PgConnectOptions::from_str(database_url)?;
-        let pool = PgPool::connect_with(options).await?;
-        let migrator = Migrator::new(std::path::Path::new("./migrations")).await?;
-        migrator.run(&pool).await?;
+    /// Opens the pool, applying pending migrations unless `auto_migrate` is off.
+    pub async fn initialize(config: &DatabaseConfig) -> Result<Self> {
+        let database = Self::connect(config).await?;
+        if config.auto_migrate {
+            database.migrate().await?;
+        }
+
+        Ok(database)
+    }
+
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self> {
+        let options = PgConnectOptions::from_str(&config.url)?;
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
+            .connect_with(options)
+            .await?;
 
         Ok(Self { pool })
     }
+
+    pub async fn migrate(&self) -> Result<()> {
+        let migrator = Migrator::new(std::path::Path::new("./migrations")).await?;
+        migrator.run(&self.pool).await?;
+
+        Ok(())
+    }
+
+    /// Versions of the migrations on disk that haven't been applied.
+    pub async fn pending_migrations(&self) -> Result<Vec<i64>> {
+        let migrator = Migrator::new(std::path::Path::new("./migrations")).await?;
+        let applied: Vec<(i64,)> =
+            sqlx::query_as(r#"SELECT version FROM _sqlx_migrations WHERE success"#)
+                .fetch_all(&self.pool)
+                .await?;
+        let applied: Vec<i64> = applied.into_iter().map(|row| row.0).collect();
+
+        Ok(migrator
+            .iter()
+            .filter(|migration| migration.migration_type.is_up_migration())
+            .map(|migration| migration.version)
+            .filter(|version| !applied.contains(version))
+            .collect())
+    }
+}
+
+/// Advisory lock key held for the duration of a sync ("itb-sync").
+const SYNC_LOCK_KEY: i64 = 0x6974_622d_7379_6e63;
+
+/// High half of the advisory lock key held while a sweep plan is approved
+/// ("itbs"); the low half is the plan id.
+const SWEEP_PLAN_LOCK_PREFIX: i64 = 0x6974_6273 << 32;
+
+/// Exclusive right to a piece of work, held as a session-level advisory lock
+/// on a dedicated connection so it spans every process sharing the database
+/// and is released if the process dies.
+pub struct Lease {
+    conn: Option<PoolConnection<Postgres>>,
+    key: i64,
+}
+
+impl Lease {
+    /// The right to sync. `None` when another process or task holds it.
+    pub async fn try_acquire_sync(pool: &PgPool) -> Result<Option<Self>> {
+        Self::try_acquire(pool, SYNC_LOCK_KEY).await
+    }
+
+    /// The right to execute a sweep plan's transfers.
+    pub async fn try_acquire_sweep_plan(pool: &PgPool, plan_id: i32) -> Result<Option<Self>> {
+        Self::try_acquire(pool, SWEEP_PLAN_LOCK_PREFIX | i64::from(plan_id)).await
+    }
+
+    async fn try_acquire(pool: &PgPool, key: i64) -> Result<Option<Self>> {
+        let mut conn = pool.acquire().await?;
+        let (acquired,): (bool,) = sqlx::query_as(r#"SELECT pg_try_advisory_lock($1)"#)
+            .bind(key)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        Ok(acquired.then(|| Self {
+            conn: Some(conn),
+            key,
+        }))
+    }
+
+    pub async fn release(mut self) -> Result<()> {
+        if let Some(mut conn) = self.conn.take() {
+            sqlx::query(r#"SELECT pg_advisory_unlock($1)"#)
+                .bind(self.key)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        // Not released (e.g. on a panic): close the connection rather than
+        // return it to the pool still holding the lock.
+        if let Some(conn) = self.conn.take() {
+            drop(conn.detach());
+        }
+    }
 }
 
 pub async fn find_transaction_id_by_uuid(pool: &PgPool, uuid: &str) -> Result<Option<i32>> {
@@ -31,21 +137,26 @@ pub async fn find_transaction_id_by_uuid(pool: &PgPool, uuid: &str) -> Result<Op
     Ok(row.map(|tuple| tuple.0))
 }
 
+/// Stores a transaction with its bucket. Returns `None` when a transaction
+/// with the same uuid was already stored, e.g. by a concurrent sync.
+#[tracing::instrument(skip_all, fields(uuid = ?tx.uuid, account_id = %tx.account_id))]
 pub async fn insert_tx_and_annotation(
     pool: &PgPool,
+    profile: &str,
     tx: &crate::clients::investec::models::Transaction,
     bucket: &str,
     notes: Option<&str>,
-) -> Result<i32> {
+) -> Result<Option<i32>> {
     let mut txn = pool.begin().await?;
 
-    let insert_tx_result = sqlx::query(
+    let inserted: Option<(i32,)> = sqlx::query_as(
         r#"
         INSERT INTO investec_transactions (
             account_id, tx_type, transaction_type, status, description,
             card_number, posted_order, posting_date, value_date, action_date,
-            transaction_date, amount, running_balance, uuid
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            transaction_date, amount, running_balance, uuid, profile
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        ON CONFLICT (uuid) DO NOTHING
         RETURNING id
         "#,
     )
@@ -63,10 +174,13 @@ pub async fn insert_tx_and_annotation(
     .bind(tx.amount)
     .bind(&tx.running_balance)
     .bind(&tx.uuid)
-    .fetch_one(&mut *txn)
+    .bind(profile)
+    .fetch_optional(&mut *txn)
     .await?;
 
-    let inserted_id: i32 = insert_tx_result.get(0);
+    let Some((inserted_id,)) = inserted else {
+        return Ok(None);
+    };
 
     sqlx::query(
         r#"
@@ -82,5 +196,1109 @@ pub async fn insert_tx_and_annotation(
     .await?;
 
     txn.commit().await?;
-    Ok(inserted_id)
+    Ok(Some(inserted_id))
+}
+
+pub async fn find_transaction_id_by_card_event(
+    pool: &PgPool,
+    card_event_id: &str,
+) -> Result<Option<i32>> {
+    let row: Option<(i32,)> =
+        sqlx::query_as(r#"SELECT id FROM investec_transactions WHERE card_event_id = $1 LIMIT 1"#)
+            .bind(card_event_id)
+            .fetch_optional(pool)
+            .await?;
+
+    Ok(row.map(|tuple| tuple.0))
+}
+
+/// Stores a pending transaction received as a card event, or fetched by a sync
+/// with pending transactions included. `occurred_at` is the card event's
+/// timestamp, kept after the row is reconciled. Returns `None` when the event
+/// was already stored.
+#[tracing::instrument(skip_all, fields(card_event_id = %card_event_id, account_id = %tx.account_id))]
+pub async fn insert_card_event(
+    pool: &PgPool,
+    profile: &str,
+    card_event_id: &str,
+    tx: &crate::clients::investec::models::Transaction,
+    bucket: &str,
+    occurred_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<Option<i32>> {
+    let mut txn = pool.begin().await?;
+
+    let inserted: Option<(i32,)> = sqlx::query_as(
+        r#"
+        INSERT INTO investec_transactions (
+            account_id, tx_type, transaction_type, status, description,
+            transaction_date, amount, card_event_id, profile, occurred_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (card_event_id) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(&tx.account_id)
+    .bind(&tx.type_)
+    .bind(&tx.transaction_type)
+    .bind(&tx.status)
+    .bind(&tx.description)
+    .bind(&tx.transaction_date)
+    .bind(tx.amount)
+    .bind(card_event_id)
+    .bind(profile)
+    .bind(occurred_at)
+    .fetch_optional(&mut *txn)
+    .await?;
+
+    let Some((inserted_id,)) = inserted else {
+        return Ok(None);
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO transaction_annotations (
+            investec_transaction_id, bucket
+        ) VALUES ($1, $2)
+        "#,
+    )
+    .bind(inserted_id)
+    .bind(bucket)
+    .execute(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+    Ok(Some(inserted_id))
+}
+
+/// Fills in a pending card-event row with the posted transaction it became,
+/// keeping the bucket it was given on arrival.
+#[tracing::instrument(skip_all, fields(uuid = ?tx.uuid, account_id = %tx.account_id))]
+pub async fn reconcile_pending_transaction(
+    pool: &PgPool,
+    tx: &crate::clients::investec::models::Transaction,
+) -> Result<Option<i32>> {
+    let mut txn = pool.begin().await?;
+    let Some(id) = lock_pending_match(&mut txn, tx).await? else {
+        return Ok(None);
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE investec_transactions SET
+            tx_type = $2, transaction_type = $3, status = $4, description = $5,
+            card_number = $6, posted_order = $7, posting_date = $8, value_date = $9,
+            action_date = $10, transaction_date = $11, amount = $12,
+            running_balance = $13, uuid = $14
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(&tx.type_)
+    .bind(&tx.transaction_type)
+    .bind(&tx.status)
+    .bind(&tx.description)
+    .bind(&tx.card_number)
+    .bind(tx.posted_order)
+    .bind(&tx.posting_date)
+    .bind(&tx.value_date)
+    .bind(&tx.action_date)
+    .bind(&tx.transaction_date)
+    .bind(tx.amount)
+    .bind(tx.running_balance)
+    .bind(&tx.uuid)
+    .execute(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+    Ok(Some(id))
+}
+
+/// The pending card-event row `tx` would be reconciled into, if any.
+pub async fn find_pending_match(
+    pool: &PgPool,
+    tx: &crate::clients::investec::models::Transaction,
+) -> Result<Option<i32>> {
+    let mut txn = pool.begin().await?;
+    lock_pending_match(&mut txn, tx).await
+}
+
+/// Locks the pending card-event row matching `tx`: same account and amount
+/// with the dates at most three days apart. Rows with unparseable dates are
+/// never matched.
+async fn lock_pending_match(
+    txn: &mut sqlx::Transaction<'_, Postgres>,
+    tx: &crate::clients::investec::models::Transaction,
+) -> Result<Option<i32>> {
+    let date = tx
+        .transaction_date
+        .as_deref()
+        .or(tx.posting_date.as_deref())
+        .or(tx.value_date.as_deref())
+        .and_then(parse_date_prefix);
+    let Some(date) = date else {
+        return Ok(None);
+    };
+
+    let candidates: Vec<(i32, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT id, transaction_date FROM investec_transactions
+        WHERE uuid IS NULL
+          AND card_event_id IS NOT NULL
+          AND status = 'PENDING'
+          AND account_id = $1
+          AND tx_type = $2
+          AND ABS(amount - $3) < 0.005
+        ORDER BY id
+        FOR UPDATE SKIP LOCKED
+        "#,
+    )
+    .bind(&tx.account_id)
+    .bind(&tx.type_)
+    .bind(tx.amount)
+    .fetch_all(&mut **txn)
+    .await?;
+
+    Ok(candidates.into_iter().find_map(|(id, stored)| {
+        let stored = stored.as_deref().and_then(parse_date_prefix)?;
+        ((stored - date).num_days().abs() <= 3).then_some(id)
+    }))
+}
+
+/// The `YYYY-MM-DD` date at the start of a stored date or timestamp.
+fn parse_date_prefix(value: &str) -> Option<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Deletes card events still pending `days` after they arrived, together with
+/// their annotations. Returns how many were removed.
+pub async fn delete_stale_card_events(pool: &PgPool, days: i32) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM investec_transactions
+        WHERE uuid IS NULL
+          AND card_event_id IS NOT NULL
+          AND status = 'PENDING'
+          AND created_at < NOW() - MAKE_INTERVAL(days => $1)
+        "#,
+    )
+    .bind(days)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn fetch_report_rows(
+    pool: &PgPool,
+    from_month: &str,
+    to_month: &str,
+    account_id: Option<&str>,
+    profile: Option<&str>,
+) -> Result<Vec<crate::reports::ReportRow>> {
+    let query = format!(
+        r#"
+        SELECT month, account_id, bucket, tx_type, description, amount
+        FROM (
+            SELECT
+                LEFT({}, 7) AS month,
+                t.account_id,
+                COALESCE(a.bucket, 'Other') AS bucket,
+                t.tx_type,
+                t.description,
+                t.amount::DOUBLE PRECISION AS amount
+            FROM investec_transactions t
+            LEFT JOIN transaction_annotations a ON a.investec_transaction_id = t.id
+            WHERE ($3::TEXT IS NULL OR t.account_id = $3)
+              AND ($4::TEXT IS NULL OR t.profile = $4)
+        ) rows
+        WHERE month BETWEEN $1 AND $2
+        ORDER BY month
+        "#,
+        TRANSACTION_DATE_SQL
+    );
+
+    let rows = sqlx::query_as::<_, crate::reports::ReportRow>(&query)
+        .bind(from_month)
+        .bind(to_month)
+        .bind(account_id)
+        .bind(profile)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
+pub async fn list_budgets(pool: &PgPool) -> Result<Vec<crate::budgets::Budget>> {
+    let budgets = sqlx::query_as::<_, crate::budgets::Budget>(
+        r#"
+        SELECT id, bucket, period, amount::DOUBLE PRECISION AS amount
+        FROM budgets
+        ORDER BY bucket, period
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(budgets)
+}
+
+pub async fn upsert_budget(pool: &PgPool, bucket: &str, period: &str, amount: f64) -> Result<i32> {
+    let row: (i32,) = sqlx::query_as(
+        r#"
+        INSERT INTO budgets (bucket, period, amount)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (bucket, period) DO UPDATE SET amount = EXCLUDED.amount
+        RETURNING id
+        "#,
+    )
+    .bind(bucket)
+    .bind(period)
+    .bind(amount)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row.0)
+}
+
+pub async fn delete_budget(pool: &PgPool, bucket: &str, period: &str) -> Result<bool> {
+    let result = sqlx::query(r#"DELETE FROM budgets WHERE bucket = $1 AND period = $2"#)
+        .bind(bucket)
+        .bind(period)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Sum of debits in `bucket` dated within `[from, to)`.
+pub async fn bucket_spend_between(
+    pool: &PgPool,
+    bucket: &str,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> Result<f64> {
+    let query = format!(
+        r#"
+        SELECT COALESCE(SUM(ABS(t.amount)), 0)::DOUBLE PRECISION
+        FROM investec_transactions t
+        JOIN transaction_annotations a ON a.investec_transaction_id = t.id
+        WHERE a.bucket = $1
+          AND t.tx_type = 'DEBIT'
+          AND {date} >= $2
+          AND {date} < $3
+        "#,
+        date = TRANSACTION_DATE_SQL
+    );
+
+    let row: (f64,) = sqlx::query_as(&query)
+        .bind(bucket)
+        .bind(from.format("%Y-%m-%d").to_string())
+        .bind(to.format("%Y-%m-%d").to_string())
+        .fetch_one(pool)
+        .await?;
+
+    Ok(row.0)
+}
+
+/// Debit spend per bucket between `from` (inclusive) and `to` (exclusive).
+pub async fn spend_by_bucket_between(
+    pool: &PgPool,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> Result<Vec<(String, f64)>> {
+    let query = format!(
+        r#"
+        SELECT a.bucket, COALESCE(SUM(ABS(t.amount)), 0)::DOUBLE PRECISION
+        FROM investec_transactions t
+        JOIN transaction_annotations a ON a.investec_transaction_id = t.id
+        WHERE t.tx_type = 'DEBIT'
+          AND {date} >= $1
+          AND {date} < $2
+        GROUP BY a.bucket
+        ORDER BY a.bucket
+        "#,
+        date = TRANSACTION_DATE_SQL
+    );
+
+    let rows: Vec<(String, f64)> = sqlx::query_as(&query)
+        .bind(from.format("%Y-%m-%d").to_string())
+        .bind(to.format("%Y-%m-%d").to_string())
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
+/// Records that `threshold` was crossed for a budget period. Returns `false`
+/// when the alert had already been recorded.
+pub async fn insert_budget_alert(
+    pool: &PgPool,
+    budget_id: i32,
+    period_start: chrono::NaiveDate,
+    threshold: i32,
+    spent: f64,
+) -> Result<bool> {
+    let row: Option<(i32,)> = sqlx::query_as(
+        r#"
+        INSERT INTO budget_alerts (budget_id, period_start, threshold, spent)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (budget_id, period_start, threshold) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(budget_id)
+    .bind(period_start)
+    .bind(threshold)
+    .bind(spent)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.is_some())
+}
+
+/// Debits dated on or after `since`, oldest first.
+pub async fn fetch_debit_history(
+    pool: &PgPool,
+    since: chrono::NaiveDate,
+) -> Result<Vec<crate::recurring::DebitHistoryRow>> {
+    let query = format!(
+        r#"
+        SELECT account_id, description, date, amount
+        FROM (
+            SELECT
+                t.account_id,
+                t.description,
+                {} AS date,
+                ABS(t.amount)::DOUBLE PRECISION AS amount
+            FROM investec_transactions t
+            WHERE t.tx_type = 'DEBIT'
+        ) rows
+        WHERE date >= $1
+        ORDER BY date
+        "#,
+        TRANSACTION_DATE_SQL
+    );
+
+    let rows = sqlx::query_as::<_, crate::recurring::DebitHistoryRow>(&query)
+        .bind(since.format("%Y-%m-%d").to_string())
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
+pub async fn list_subscriptions(pool: &PgPool) -> Result<Vec<crate::recurring::Subscription>> {
+    let subscriptions = sqlx::query_as::<_, crate::recurring::Subscription>(
+        r#"
+        SELECT id, account_id, merchant, description, cadence,
+               expected_amount::DOUBLE PRECISION AS expected_amount, occurrences,
+               last_seen_date, next_expected_date, status
+        FROM subscriptions
+        ORDER BY next_expected_date
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(subscriptions)
+}
+
+pub async fn upsert_subscription(
+    pool: &PgPool,
+    subscription: &crate::recurring::DetectedSubscription,
+    status: &str,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO subscriptions (
+            account_id, merchant, description, cadence, expected_amount,
+            occurrences, last_seen_date, next_expected_date, status
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (account_id, merchant) DO UPDATE SET
+            description = EXCLUDED.description,
+            cadence = EXCLUDED.cadence,
+            expected_amount = EXCLUDED.expected_amount,
+            occurrences = EXCLUDED.occurrences,
+            last_seen_date = EXCLUDED.last_seen_date,
+            next_expected_date = EXCLUDED.next_expected_date,
+            status = EXCLUDED.status
+        "#,
+    )
+    .bind(&subscription.account_id)
+    .bind(&subscription.merchant)
+    .bind(&subscription.description)
+    .bind(subscription.cadence.as_str())
+    .bind(subscription.expected_amount)
+    .bind(subscription.occurrences as i32)
+    .bind(subscription.last_seen_date)
+    .bind(subscription.next_expected_date)
+    .bind(status)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_subscription_status(pool: &PgPool, id: i32, status: &str) -> Result<()> {
+    sqlx::query(r#"UPDATE subscriptions SET status = $2 WHERE id = $1"#)
+        .bind(id)
+        .bind(status)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Debits dated on or after `since` together with their bucket.
+pub async fn fetch_anomaly_history(
+    pool: &PgPool,
+    since: chrono::NaiveDate,
+) -> Result<Vec<crate::anomaly::AnomalyHistoryRow>> {
+    let query = format!(
+        r#"
+        SELECT id, account_id, description, bucket, amount, occurred_at
+        FROM (
+            SELECT
+                t.id,
+                t.account_id,
+                t.description,
+                a.bucket,
+                {} AS date,
+                ABS(t.amount)::DOUBLE PRECISION AS amount,
+                t.occurred_at
+            FROM investec_transactions t
+            LEFT JOIN transaction_annotations a ON a.investec_transaction_id = t.id
+            WHERE t.tx_type = 'DEBIT'
+        ) rows
+        WHERE date >= $1
+        ORDER BY id
+        "#,
+        TRANSACTION_DATE_SQL
+    );
+
+    let rows = sqlx::query_as::<_, crate::anomaly::AnomalyHistoryRow>(&query)
+        .bind(since.format("%Y-%m-%d").to_string())
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
+pub async fn set_annotation_anomalies(
+    pool: &PgPool,
+    investec_transaction_id: i32,
+    anomalies: &[&str],
+) -> Result<()> {
+    sqlx::query(
+        r#"UPDATE transaction_annotations SET anomalies = $2 WHERE investec_transaction_id = $1"#,
+    )
+    .bind(investec_transaction_id)
+    .bind(anomalies)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_sweep_rules(pool: &PgPool) -> Result<Vec<crate::sweeps::SweepRule>> {
+    let rules = sqlx::query_as::<_, crate::sweeps::SweepRule>(
+        r#"
+        SELECT id, kind, bucket, from_account_id, to_account_id,
+               round_to::DOUBLE PRECISION AS round_to
+        FROM sweep_rules
+        ORDER BY id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rules)
+}
+
+pub async fn insert_sweep_rule(
+    pool: &PgPool,
+    kind: &str,
+    bucket: &str,
+    from_account_id: &str,
+    to_account_id: &str,
+    round_to: Option<f64>,
+) -> Result<i32> {
+    let row: (i32,) = sqlx::query_as(
+        r#"
+        INSERT INTO sweep_rules (kind, bucket, from_account_id, to_account_id, round_to)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(kind)
+    .bind(bucket)
+    .bind(from_account_id)
+    .bind(to_account_id)
+    .bind(round_to)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row.0)
+}
+
+pub async fn delete_sweep_rule(pool: &PgPool, id: i32) -> Result<bool> {
+    let result = sqlx::query(r#"DELETE FROM sweep_rules WHERE id = $1"#)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Debit amounts in `bucket` on `account_id` dated within `[from, to)`.
+pub async fn bucket_debits_between(
+    pool: &PgPool,
+    bucket: &str,
+    account_id: &str,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> Result<Vec<f64>> {
+    let query = format!(
+        r#"
+        SELECT ABS(t.amount)::DOUBLE PRECISION
+        FROM investec_transactions t
+        JOIN transaction_annotations a ON a.investec_transaction_id = t.id
+        WHERE a.bucket = $1
+          AND t.account_id = $2
+          AND t.tx_type = 'DEBIT'
+          AND {date} >= $3
+          AND {date} < $4
+        ORDER BY t.id
+        "#,
+        date = TRANSACTION_DATE_SQL
+    );
+
+    let rows: Vec<(f64,)> = sqlx::query_as(&query)
+        .bind(bucket)
+        .bind(account_id)
+        .bind(from.format("%Y-%m-%d").to_string())
+        .bind(to.format("%Y-%m-%d").to_string())
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.into_iter().map(|row| row.0).collect())
+}
+
+/// Stores a plan and its items. Returns `None` when the period already has a
+/// plan.
+pub async fn insert_sweep_plan(
+    pool: &PgPool,
+    period_start: chrono::NaiveDate,
+    period_end: chrono::NaiveDate,
+    instructions: &[crate::sweeps::SweepInstruction],
+) -> Result<Option<i32>> {
+    let mut tx = pool.begin().await?;
+
+    let plan_id: Option<(i32,)> = sqlx::query_as(
+        r#"
+        INSERT INTO sweep_plans (period_start, period_end)
+        VALUES ($1, $2)
+        ON CONFLICT (period_start) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(period_start)
+    .bind(period_end)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((plan_id,)) = plan_id else {
+        return Ok(None);
+    };
+
+    for instruction in instructions {
+        sqlx::query(
+            r#"
+            INSERT INTO sweep_plan_items (
+                plan_id, rule_id, from_account_id, to_account_id, amount, reference, reason
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(plan_id)
+        .bind(instruction.rule_id)
+        .bind(&instruction.from_account_id)
+        .bind(&instruction.to_account_id)
+        .bind(instruction.amount)
+        .bind(&instruction.reference)
+        .bind(&instruction.reason)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(Some(plan_id))
+}
+
+pub async fn list_sweep_plans(pool: &PgPool) -> Result<Vec<crate::sweeps::SweepPlan>> {
+    let plans = sqlx::query_as::<_, crate::sweeps::SweepPlan>(
+        r#"
+        SELECT id, period_start, period_end, status, created_at, approved_at
+        FROM sweep_plans
+        ORDER BY period_start DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(plans)
+}
+
+pub async fn find_sweep_plan(pool: &PgPool, id: i32) -> Result<Option<crate::sweeps::SweepPlan>> {
+    let plan = sqlx::query_as::<_, crate::sweeps::SweepPlan>(
+        r#"
+        SELECT id, period_start, period_end, status, created_at, approved_at
+        FROM sweep_plans
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(plan)
+}
+
+pub async fn list_sweep_plan_items(
+    pool: &PgPool,
+    plan_id: i32,
+) -> Result<Vec<crate::sweeps::SweepPlanItem>> {
+    let items = sqlx::query_as::<_, crate::sweeps::SweepPlanItem>(
+        r#"
+        SELECT id, from_account_id, to_account_id,
+               amount::DOUBLE PRECISION AS amount, reference, reason, status,
+               payment_reference, error
+        FROM sweep_plan_items
+        WHERE plan_id = $1
+        ORDER BY id
+        "#,
+    )
+    .bind(plan_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(items)
+}
+
+pub async fn set_sweep_item_result(
+    pool: &PgPool,
+    id: i32,
+    status: &str,
+    payment_reference: Option<&str>,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE sweep_plan_items
+        SET status = $2, payment_reference = $3, error = $4, executed_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(status)
+    .bind(payment_reference)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_sweep_plan_status(pool: &PgPool, id: i32, status: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE sweep_plans
+        SET status = $2, approved_at = COALESCE(approved_at, NOW())
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(status)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Sets the status of an item whose outcome was unknown. Returns its plan id,
+/// or `None` when there is no such unknown item.
+pub async fn confirm_sweep_item(pool: &PgPool, id: i32, status: &str) -> Result<Option<i32>> {
+    let plan_id: Option<(i32,)> = sqlx::query_as(
+        r#"
+        UPDATE sweep_plan_items
+        SET status = $2
+        WHERE id = $1 AND status = 'unknown'
+        RETURNING plan_id
+        "#,
+    )
+    .bind(id)
+    .bind(status)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(plan_id.map(|row| row.0))
+}
+
+/// Marks a failed plan executed once every one of its items is.
+pub async fn complete_sweep_plan(pool: &PgPool, id: i32) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE sweep_plans
+        SET status = 'executed'
+        WHERE id = $1
+          AND status = 'failed'
+          AND NOT EXISTS (
+              SELECT 1 FROM sweep_plan_items
+              WHERE plan_id = $1 AND status <> 'executed'
+          )
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_card_rules(pool: &PgPool) -> Result<Vec<crate::card_code::CardRule>> {
+    let rules = sqlx::query_as::<_, crate::card_code::CardRule>(
+        r#"
+        SELECT id, bucket, merchant_category_code
+        FROM card_rules
+        ORDER BY bucket, merchant_category_code
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rules)
+}
+
+/// Returns `None` when the bucket already has a rule for the category.
+pub async fn insert_card_rule(
+    pool: &PgPool,
+    bucket: &str,
+    merchant_category_code: &str,
+) -> Result<Option<i32>> {
+    let row: Option<(i32,)> = sqlx::query_as(
+        r#"
+        INSERT INTO card_rules (bucket, merchant_category_code)
+        VALUES ($1, $2)
+        ON CONFLICT (bucket, merchant_category_code) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(bucket)
+    .bind(merchant_category_code)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|tuple| tuple.0))
+}
+
+pub async fn delete_card_rule(pool: &PgPool, id: i32) -> Result<bool> {
+    let result = sqlx::query(r#"DELETE FROM card_rules WHERE id = $1"#)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Records code saved to a card as its next version and returns the version.
+pub async fn insert_card_code_deployment(
+    pool: &PgPool,
+    card_key: &str,
+    code_id: &str,
+    code: &str,
+    published: bool,
+) -> Result<i32> {
+    let mut txn = pool.begin().await?;
+
+    // Serialises concurrent deploys to the same card.
+    sqlx::query(r#"SELECT pg_advisory_xact_lock(hashtext($1))"#)
+        .bind(card_key)
+        .execute(&mut *txn)
+        .await?;
+
+    let row: (i32,) = sqlx::query_as(
+        r#"
+        INSERT INTO card_code_deployments (card_key, version, code_id, code, published)
+        SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3, $4
+        FROM card_code_deployments
+        WHERE card_key = $1
+        RETURNING version
+        "#,
+    )
+    .bind(card_key)
+    .bind(code_id)
+    .bind(code)
+    .bind(published)
+    .fetch_one(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+    Ok(row.0)
+}
+
+pub async fn list_card_code_deployments(
+    pool: &PgPool,
+    card_key: &str,
+) -> Result<Vec<crate::card_code::CardCodeDeployment>> {
+    let deployments = sqlx::query_as::<_, crate::card_code::CardCodeDeployment>(
+        r#"
+        SELECT version, code_id, code, published, created_at
+        FROM card_code_deployments
+        WHERE card_key = $1
+        ORDER BY version DESC
+        "#,
+    )
+    .bind(card_key)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(deployments)
+}
+
+/// A specific version of a card's code, or the latest when `version` is `None`.
+pub async fn find_card_code_deployment(
+    pool: &PgPool,
+    card_key: &str,
+    version: Option<i32>,
+) -> Result<Option<crate::card_code::CardCodeDeployment>> {
+    let deployment = sqlx::query_as::<_, crate::card_code::CardCodeDeployment>(
+        r#"
+        SELECT version, code_id, code, published, created_at
+        FROM card_code_deployments
+        WHERE card_key = $1 AND ($2::INTEGER IS NULL OR version = $2)
+        ORDER BY version DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(card_key)
+    .bind(version)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(deployment)
+}
+
+/// Recent transactions still in `bucket`, dated on or after `since`.
+pub async fn list_transactions_in_bucket(
+    pool: &PgPool,
+    bucket: &str,
+    since: chrono::NaiveDate,
+) -> Result<Vec<(i32, crate::clients::investec::models::Transaction)>> {
+    let query = format!(
+        r#"
+        SELECT t.id, t.account_id, t.tx_type, t.transaction_type, t.status, t.description,
+               t.transaction_date, t.amount::DOUBLE PRECISION AS amount, t.uuid
+        FROM investec_transactions t
+        JOIN transaction_annotations a ON a.investec_transaction_id = t.id
+        WHERE a.bucket = $1 AND {} >= $2
+        ORDER BY t.id
+        "#,
+        TRANSACTION_DATE_SQL
+    );
+
+    let rows = sqlx::query(&query)
+        .bind(bucket)
+        .bind(since.format("%Y-%m-%d").to_string())
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.get("id"),
+                crate::clients::investec::models::Transaction {
+                    account_id: row.get("account_id"),
+                    type_: row.get("tx_type"),
+                    transaction_type: row.get("transaction_type"),
+                    status: row.get("status"),
+                    description: row.get("description"),
+                    card_number: None,
+                    posted_order: None,
+                    posting_date: None,
+                    value_date: None,
+                    action_date: None,
+                    transaction_date: row.get("transaction_date"),
+                    amount: row.get("amount"),
+                    running_balance: None,
+                    uuid: row.get("uuid"),
+                },
+            )
+        })
+        .collect())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn set_annotation_bucket(pool: &PgPool, transaction_id: i32, bucket: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE transaction_annotations
+        SET bucket = $2
+        WHERE investec_transaction_id = $1
+        "#,
+    )
+    .bind(transaction_id)
+    .bind(bucket)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all, fields(profile = %profile, account_id = %balance.account_id))]
+pub async fn insert_balance_snapshot(
+    pool: &PgPool,
+    profile: &str,
+    balance: &crate::clients::investec::models::Balance,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO balance_snapshots (
+            profile, account_id, current_balance, available_balance, currency
+        ) VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(profile)
+    .bind(&balance.account_id)
+    .bind(balance.current_balance)
+    .bind(balance.available_balance)
+    .bind(&balance.currency)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_scheduled_jobs(pool: &PgPool) -> Result<Vec<crate::scheduler::JobStatus>> {
+    let jobs = sqlx::query_as::<_, crate::scheduler::JobStatus>(
+        r#"
+        SELECT name, schedule, status, last_started_at, last_finished_at, last_error,
+               next_run_at, skipped_runs
+        FROM scheduled_jobs
+        ORDER BY name
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(jobs)
+}
+
+/// Records the jobs this process schedules and forgets the ones it doesn't.
+pub async fn register_scheduled_jobs(
+    pool: &PgPool,
+    jobs: &[(&str, &str, Option<chrono::DateTime<chrono::Utc>>)],
+) -> Result<()> {
+    let mut txn = pool.begin().await?;
+
+    let names: Vec<&str> = jobs.iter().map(|(name, _, _)| *name).collect();
+    sqlx::query(r#"DELETE FROM scheduled_jobs WHERE name <> ALL($1)"#)
+        .bind(&names)
+        .execute(&mut *txn)
+        .await?;
+
+    for (name, schedule, next_run_at) in jobs {
+        sqlx::query(
+            r#"
+            INSERT INTO scheduled_jobs (name, schedule, next_run_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (name) DO UPDATE
+            SET schedule = EXCLUDED.schedule, next_run_at = EXCLUDED.next_run_at
+            "#,
+        )
+        .bind(name)
+        .bind(schedule)
+        .bind(next_run_at)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    txn.commit().await?;
+    Ok(())
+}
+
+pub async fn record_job_started(pool: &PgPool, name: &str, schedule: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO scheduled_jobs (name, schedule, status, last_started_at)
+        VALUES ($1, $2, 'running', NOW())
+        ON CONFLICT (name) DO UPDATE
+        SET status = 'running', last_started_at = NOW(), last_error = NULL
+        "#,
+    )
+    .bind(name)
+    .bind(schedule)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn record_job_finished(
+    pool: &PgPool,
+    name: &str,
+    error: Option<&str>,
+    next_run_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE scheduled_jobs
+        SET status = CASE WHEN $2::TEXT IS NULL THEN 'succeeded' ELSE 'failed' END,
+            last_finished_at = NOW(),
+            last_succeeded_at = CASE WHEN $2::TEXT IS NULL THEN NOW() ELSE last_succeeded_at END,
+            last_error = $2,
+            next_run_at = COALESCE($3, next_run_at)
+        WHERE name = $1
+        "#,
+    )
+    .bind(name)
+    .bind(error)
+    .bind(next_run_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn record_job_skipped(
+    pool: &PgPool,
+    name: &str,
+    next_run_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE scheduled_jobs
+        SET skipped_runs = skipped_runs + 1, next_run_at = COALESCE($2, next_run_at)
+        WHERE name = $1
+        "#,
+    )
+    .bind(name)
+    .bind(next_run_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// When `name` last finished without an error.
+pub async fn job_last_succeeded_at(
+    pool: &PgPool,
+    name: &str,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+    let row: Option<(Option<chrono::DateTime<chrono::Utc>>,)> =
+        sqlx::query_as(r#"SELECT last_succeeded_at FROM scheduled_jobs WHERE name = $1"#)
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+
+    Ok(row.and_then(|row| row.0))
 }
@@ -0,0 +1,224 @@
+//! Liveness and readiness endpoints for Docker and Kubernetes.
+//!
+//! `/healthz` answers as long as the process runs. `/readyz` checks the
+//! dependencies a sync needs and answers `503` with the failing checks when
+//! any of them is down.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::Utc;
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::bucket_classifier::BucketClassifier;
+use crate::clients::InvestecClient;
+use crate::db;
+use crate::scheduler::JobKind;
+use crate::shutdown::Shutdown;
+
+/// Longest a single readiness check may take.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct HealthState {
+    pub database: Arc<db::Database>,
+    pub clients: Arc<Vec<InvestecClient>>,
+    pub classifier: Arc<BucketClassifier>,
+    /// `None` when the sync isn't scheduled, so its age isn't checked.
+    pub max_sync_age: Option<chrono::Duration>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, result: Result<()>) -> Self {
+        Self {
+            name: name.into(),
+            ok: result.is_ok(),
+            detail: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+impl HealthState {
+    /// Runs every check at once, so a slow dependency costs at most
+    /// [`CHECK_TIMEOUT`] in total.
+    pub async fn readiness(&self) -> Readiness {
+        let tokens: Vec<_> = (0..self.clients.len())
+            .map(|index| {
+                let clients = Arc::clone(&self.clients);
+                tokio::spawn(async move {
+                    let client = &clients[index];
+                    let result = timed(async { Ok(client.ensure_token().await?) }).await;
+                    Check::new(format!("investec_token:{}", client.profile()), result)
+                })
+            })
+            .collect();
+
+        let (database, migrations, classifier, last_sync) = tokio::join!(
+            timed(self.check_database()),
+            timed(self.check_migrations()),
+            timed(self.check_classifier()),
+            async {
+                match self.max_sync_age {
+                    Some(max_age) => Some(timed(self.check_last_sync(max_age)).await),
+                    None => None,
+                }
+            },
+        );
+
+        let mut checks = vec![
+            Check::new("database", database),
+            Check::new("migrations", migrations),
+        ];
+        for token in tokens {
+            checks.push(
+                token
+                    .await
+                    .unwrap_or_else(|e| Check::new("investec_token", Err(e.into()))),
+            );
+        }
+        checks.push(Check::new("classifier", classifier));
+        if let Some(last_sync) = last_sync {
+            checks.push(Check::new("last_sync", last_sync));
+        }
+
+        Readiness {
+            ready: checks.iter().all(|check| check.ok),
+            checks,
+        }
+    }
+
+    async fn check_database(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.database.pool).await?;
+        Ok(())
+    }
+
+    async fn check_migrations(&self) -> Result<()> {
+        let pending = self.database.pending_migrations().await?;
+        if !pending.is_empty() {
+            anyhow::bail!("{} pending, run the migrate command", pending.len());
+        }
+        Ok(())
+    }
+
+    /// At least one provider must answer; the rest are reported in the detail.
+    async fn check_classifier(&self) -> Result<()> {
+        let checks = self.classifier.check_providers().await;
+        if checks.iter().any(|(_, result)| result.is_ok()) {
+            return Ok(());
+        }
+
+        let failures: Vec<String> = checks
+            .iter()
+            .filter_map(|(name, result)| result.as_ref().err().map(|e| format!("{}: {}", name, e)))
+            .collect();
+        if failures.is_empty() {
+            anyhow::bail!("no classification provider configured");
+        }
+        anyhow::bail!("{}", failures.join("; "))
+    }
+
+    async fn check_last_sync(&self, max_age: chrono::Duration) -> Result<()> {
+        let last = db::job_last_succeeded_at(&self.database.pool, JobKind::Sync.as_str()).await?;
+        match last {
+            Some(last) if Utc::now() - last <= max_age => Ok(()),
+            Some(last) => anyhow::bail!("last successful sync at {}", last.to_rfc3339()),
+            None => anyhow::bail!("no successful sync yet"),
+        }
+    }
+}
+
+async fn timed(check: impl Future<Output = Result<()>>) -> Result<()> {
+    tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after {:?}", CHECK_TIMEOUT)))
+}
+
+pub fn router(state: Arc<HealthState>) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state)
+}
+
+pub async fn spawn(
+    addr: &str,
+    state: Arc<HealthState>,
+    shutdown: Arc<Shutdown>,
+) -> Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+
+    let handle = tokio::spawn(async move {
+        let server = axum::serve(listener, router(state))
+            .with_graceful_shutdown(async move { shutdown.requested().await });
+        if let Err(e) = server.await {
+            tracing::error!(error = %e, "Health server stopped");
+        }
+    });
+
+    Ok((local_addr, handle))
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(state): State<Arc<HealthState>>) -> Response {
+    let readiness = state.readiness().await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(readiness)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_json() {
+        let readiness = Readiness {
+            ready: false,
+            checks: vec![
+                Check::new("database", Ok(())),
+                Check::new("last_sync", Err(anyhow::anyhow!("no successful sync yet"))),
+            ],
+        };
+
+        assert_eq!(
+            serde_json::to_value(&readiness).unwrap(),
+            serde_json::json!({
+                "ready": false,
+                "checks": [
+                    { "name": "database", "ok": true },
+                    { "name": "last_sync", "ok": false, "detail": "no successful sync yet" }
+                ]
+            })
+        );
+    }
+}
@@ -1,28 +1,136 @@
+mod anomaly;
 mod bucket_classifier;
+mod budgets;
+mod card_code;
+mod card_webhook;
 mod clients;
+mod commands;
 mod config;
 mod db;
+mod health;
+mod mock_investec;
+mod monitoring;
+mod notifications;
+mod recurring;
+mod reports;
 mod scheduler;
+mod shutdown;
+mod sweeps;
+mod telemetry;
 
+use clap::{Parser, Subcommand};
 use config::settings::load_config;
 
 use crate::bucket_classifier::BucketClassifier;
 use crate::clients::InvestecClient;
+use crate::notifications::Notifier;
 
 use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Fetches Investec transactions and buckets them using AI"
+)]
+struct Cli {
+    /// TOML or YAML config file; environment variables override its settings
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
+    /// Only use this Investec profile (from INVESTEC_PROFILES); commands that
+    /// talk to a single profile otherwise use the first one
+    #[arg(long)]
+    profile: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run an initial sync and then the scheduled jobs until stopped (default)
+    Run,
+    /// List Investec accounts with their current balances
+    Accounts(commands::accounts::AccountsArgs),
+    /// Print a monthly spending report per bucket
+    Report(commands::report::ReportArgs),
+    /// Manage per-bucket spending budgets
+    Budget(commands::budget::BudgetArgs),
+    /// List recurring charges detected from stored transactions
+    Subscriptions(commands::subscriptions::SubscriptionsArgs),
+    /// Manage sweep rules and review or approve sweep plans
+    Sweep(commands::sweep::SweepArgs),
+    /// List beneficiaries, transfer between accounts and pay beneficiaries
+    Payments(commands::payments::PaymentsArgs),
+    /// Generate, deploy and simulate programmable card code
+    Card(commands::card::CardArgs),
+    /// Inspect the effective configuration
+    Config(commands::config::ConfigArgs),
+    /// Show scheduled job status or run a job now
+    Jobs(commands::jobs::JobsArgs),
+    /// Apply pending database migrations
+    Migrate,
+    /// Serve a local mock of the Investec API with seeded fixtures
+    MockInvestec {
+        #[arg(long, default_value = "127.0.0.1:8089")]
+        listen: String,
+        /// Transactions returned per page
+        #[arg(long, default_value_t = 100)]
+        page_size: usize,
+    },
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .init();
+    let cli = Cli::parse();
+
+    if let Some(Command::MockInvestec { listen, page_size }) = &cli.command {
+        let _telemetry = telemetry::init(&config::settings::TelemetryConfig::default())?;
+        return mock_investec::serve(listen, *page_size).await;
+    }
+
+    // Logging depends on the config, so config errors go straight to stderr.
+    let mut config = load_config(cli.config.as_deref())?;
+    let telemetry = telemetry::init(&config.telemetry)?;
+    if let Some(profile) = &cli.profile {
+        config.select_profile(profile)?;
+    }
+
+    let result = match cli.command.unwrap_or(Command::Run) {
+        Command::Run => run(config).await,
+        Command::Accounts(args) => commands::accounts::run(config, args).await,
+        Command::Report(args) => commands::report::run(config, args).await,
+        Command::Budget(args) => commands::budget::run(config, args).await,
+        Command::Subscriptions(args) => commands::subscriptions::run(config, args).await,
+        Command::Sweep(args) => commands::sweep::run(config, args).await,
+        Command::Payments(args) => commands::payments::run(config, args).await,
+        Command::Card(args) => commands::card::run(config, args).await,
+        Command::Config(args) => commands::config::run(config, args).await,
+        Command::Jobs(args) => commands::jobs::run(config, args).await,
+        Command::Migrate => migrate(config).await,
+        Command::MockInvestec { .. } => unreachable!("handled before loading config"),
+    };
+
+    telemetry.shutdown();
+    result
+}
 
-    let config = load_config();
+async fn migrate(config: config::settings::Config) -> anyhow::Result<()> {
+    let database = db::Database::connect(&config.database).await?;
+    database.migrate().await?;
+    println!("Database is up to date");
+    Ok(())
+}
 
-    let investec_client = InvestecClient::new(config.clone())?;
+async fn run(config: config::settings::Config) -> anyhow::Result<()> {
+    let investec_clients = InvestecClient::for_profiles(&config)?;
+    tracing::info!(
+        profiles = ?config
+            .investec_profiles
+            .iter()
+            .map(|profile| profile.profile.as_str())
+            .collect::<Vec<_>>(),
+        "Syncing Investec profiles"
+    );
 
     let ollama_available = config.is_ollama_available();
     let gemini_available = config.is_gemini_available();
@@ -40,21 +148,134 @@ async fn main() -> anyhow::Result<()> {
         tracing::info!("Google Search configuration available");
     }
 
-    let database = db::Database::initialize(&config.database.url).await?;
+    let notifier = Notifier::from_config(&config.notifications)?;
+    if !notifier.sink_names().is_empty() {
+        tracing::info!(sinks = ?notifier.sink_names(), "Notifications enabled");
+    }
+
+    // Migrations run once here; every scheduled run shares this pool.
+    let database = Arc::new(db::Database::initialize(&config.database).await?);
 
-    scheduler::run_sync(&investec_client, &bucket_classifier, &database).await;
+    let shutdown = Arc::new(shutdown::Shutdown::new());
+    let signal_shutdown = Arc::clone(&shutdown);
+    tokio::spawn(async move {
+        match shutdown::signal().await {
+            Ok(signal) => tracing::info!(signal, "Shutting down"),
+            Err(e) => tracing::error!(error = %e, "Failed to listen for signals, shutting down"),
+        }
+        signal_shutdown.request();
+    });
 
-    let client_arc = Arc::new(investec_client);
+    let mut metrics_server = None;
+    if let Some(listen) = &config.metrics.listen {
+        let handle = monitoring::install()?;
+        let (addr, server) =
+            monitoring::spawn(listen, handle, database.pool.clone(), Arc::clone(&shutdown)).await?;
+        tracing::info!("Metrics available on http://{}/metrics", addr);
+        metrics_server = Some(server);
+    }
+
+    let clients_arc = Arc::new(investec_clients);
     let classifier_arc = Arc::new(bucket_classifier);
+    let notifier_arc = Arc::new(notifier);
+    let context = Arc::new(scheduler::JobContext {
+        clients: Arc::clone(&clients_arc),
+        classifier: Arc::clone(&classifier_arc),
+        notifier: Arc::clone(&notifier_arc),
+        sync_config: config.sync.clone(),
+        reclassify_days: config.schedules.reclassify_days,
+        shutdown: Arc::clone(&shutdown),
+    });
+
+    let mut health_server = None;
+    if let Some(listen) = &config.health.listen {
+        let state = health::HealthState {
+            database: Arc::clone(&database),
+            clients: Arc::clone(&clients_arc),
+            classifier: Arc::clone(&classifier_arc),
+            max_sync_age: config
+                .schedules
+                .sync
+                .as_ref()
+                .map(|_| chrono::Duration::hours(config.health.max_sync_age_hours as i64)),
+        };
+        let (addr, server) = health::spawn(listen, Arc::new(state), Arc::clone(&shutdown)).await?;
+        tracing::info!("Health checks on http://{}/healthz and /readyz", addr);
+        health_server = Some(server);
+    }
+
+    if let Some(_job) = shutdown.start_job() {
+        let _ = scheduler::run_recorded(
+            scheduler::JobKind::Sync,
+            config.schedules.sync.as_deref(),
+            &context,
+            &database,
+        )
+        .await;
+    }
+
+    let mut webhook = None;
+    if let (Some(listen), Some(secret)) = (&config.card_webhook.listen, &config.card_webhook.secret)
+        && !shutdown.is_requested()
+    {
+        let state = card_webhook::WebhookState::new(
+            secret.clone(),
+            Arc::clone(&clients_arc),
+            Arc::clone(&classifier_arc),
+            Arc::clone(&notifier_arc),
+            database.pool.clone(),
+        );
+        let (addr, handle) =
+            card_webhook::spawn(listen, Arc::new(state), Arc::clone(&shutdown)).await?;
+        tracing::info!("Card webhook listening on http://{}/card-events", addr);
+        webhook = Some(handle);
+    }
+
+    let mut job_scheduler = None;
+    if !shutdown.is_requested() {
+        job_scheduler =
+            Some(scheduler::start(context, &config.schedules, Arc::clone(&database)).await?);
+        tracing::info!("Scheduler started");
+    }
 
-    let scheduler =
-        scheduler::start_hourly(client_arc, classifier_arc, config.database.url.clone()).await?;
+    shutdown.requested().await;
 
-    tracing::info!("Scheduler started. Sync runs every hour at :00");
+    // No new runs from here on; running jobs stop at their next safe point.
+    if let Some(mut job_scheduler) = job_scheduler
+        && let Err(e) = job_scheduler.shutdown().await
+    {
+        tracing::warn!(error = %e, "Failed to stop the scheduler");
+    }
 
-    tokio::signal::ctrl_c().await?;
+    let timeout = Duration::from_secs(config.schedules.shutdown_timeout_secs);
+    let deadline = tokio::time::Instant::now() + timeout;
+    let finished = shutdown.wait_idle(timeout).await;
+    if let Some(handle) = webhook
+        && tokio::time::timeout_at(deadline, handle).await.is_err()
+    {
+        tracing::warn!("Card webhook requests still open at the shutdown deadline");
+    }
+    if let Some(server) = metrics_server
+        && tokio::time::timeout_at(deadline, server).await.is_err()
+    {
+        tracing::warn!("Metrics requests still open at the shutdown deadline");
+    }
+    if let Some(server) = health_server
+        && tokio::time::timeout_at(deadline, server).await.is_err()
+    {
+        tracing::warn!("Health check requests still open at the shutdown deadline");
+    }
 
-    drop(scheduler);
+    if !finished {
+        // Closing the pool would wait for the running jobs' connections.
+        anyhow::bail!(
+            "Shutdown deadline of {}s passed with {} job(s) still running",
+            timeout.as_secs(),
+            shutdown.running_jobs()
+        );
+    }
 
+    database.pool.close().await;
+    tracing::info!("Shutdown complete");
     Ok(())
 }
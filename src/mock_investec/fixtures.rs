@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use chrono::{Days, NaiveDate};
+use serde_json::{Value, json};
+
+pub const CHEQUE_ACCOUNT_ID: &str = "mock-cheque-account";
+pub const SAVINGS_ACCOUNT_ID: &str = "mock-savings-account";
+pub const CARD_KEY: &str = "mock-card";
+
+pub struct Fixtures {
+    pub accounts: Vec<Value>,
+    pub transactions: HashMap<String, Vec<Value>>,
+    pub pending: HashMap<String, Vec<Value>>,
+    pub balances: HashMap<String, Value>,
+    pub beneficiaries: Vec<Value>,
+    pub cards: Vec<Value>,
+}
+
+impl Fixtures {
+    /// Seeds accounts, balances and roughly three months of transactions
+    /// ending on `today`, so a sync against the mock always finds new data.
+    pub fn seed(today: NaiveDate) -> Self {
+        let accounts = vec![
+            account(CHEQUE_ACCOUNT_ID, "10010206147", "Private Bank Account"),
+            account(SAVINGS_ACCOUNT_ID, "10010206155", "PrimeSaver"),
+        ];
+
+        let mut cheque = Vec::new();
+        let mut savings = Vec::new();
+
+        for month in 0..3u64 {
+            let offset = month * 30;
+            cheque.push(transaction(
+                CHEQUE_ACCOUNT_ID,
+                today - Days::new(offset + 2),
+                "CREDIT",
+                "SALARY ACME CORP",
+                45000.0,
+                None,
+            ));
+            cheque.push(transaction(
+                CHEQUE_ACCOUNT_ID,
+                today - Days::new(offset + 5),
+                "DEBIT",
+                "NETFLIX.COM 866-579-7172 LOS GATOS",
+                199.0,
+                Some("CardPurchases"),
+            ));
+            cheque.push(transaction(
+                CHEQUE_ACCOUNT_ID,
+                today - Days::new(offset + 6),
+                "DEBIT",
+                "CITY OF CAPE TOWN ELECTRICITY",
+                1450.0 + month as f64 * 10.0,
+                Some("DebitOrders"),
+            ));
+            cheque.push(transaction(
+                CHEQUE_ACCOUNT_ID,
+                today - Days::new(offset + 3),
+                "DEBIT",
+                "TRANSFER TO PRIMESAVER",
+                5000.0,
+                Some("Transfers"),
+            ));
+            savings.push(transaction(
+                SAVINGS_ACCOUNT_ID,
+                today - Days::new(offset + 3),
+                "CREDIT",
+                "TRANSFER FROM PRIVATE BANK ACCOUNT",
+                5000.0,
+                Some("Transfers"),
+            ));
+        }
+
+        for week in 0..12u64 {
+            cheque.push(transaction(
+                CHEQUE_ACCOUNT_ID,
+                today - Days::new(week * 7 + 1),
+                "DEBIT",
+                "WOOLWORTHS CAVENDISH",
+                850.0 + (week % 3) as f64 * 40.0,
+                Some("CardPurchases"),
+            ));
+        }
+
+        cheque.push(transaction(
+            CHEQUE_ACCOUNT_ID,
+            today,
+            "DEBIT",
+            "UBER TRIP HELP.UBER.COM",
+            87.5,
+            Some("CardPurchases"),
+        ));
+        cheque.push(transaction(
+            CHEQUE_ACCOUNT_ID,
+            today,
+            "DEBIT",
+            "STEAMGAMES.COM 4259522985 US",
+            349.0,
+            Some("CardPurchases"),
+        ));
+
+        let mut card_hold = transaction(
+            CHEQUE_ACCOUNT_ID,
+            today,
+            "DEBIT",
+            "PICK N PAY CLAREMONT",
+            412.3,
+            Some("CardPurchases"),
+        );
+        card_hold["status"] = json!("PENDING");
+        card_hold["postingDate"] = Value::Null;
+        card_hold["uuid"] = Value::Null;
+
+        let balances = HashMap::from([
+            (
+                CHEQUE_ACCOUNT_ID.to_string(),
+                balance(CHEQUE_ACCOUNT_ID, 28450.32, 58450.32),
+            ),
+            (
+                SAVINGS_ACCOUNT_ID.to_string(),
+                balance(SAVINGS_ACCOUNT_ID, 120500.0, 120500.0),
+            ),
+        ]);
+
+        Self {
+            accounts,
+            transactions: HashMap::from([
+                (CHEQUE_ACCOUNT_ID.to_string(), cheque),
+                (SAVINGS_ACCOUNT_ID.to_string(), savings),
+            ]),
+            beneficiaries: vec![
+                beneficiary("mock-beneficiary-landlord", "Landlord", "62000000001"),
+                beneficiary("mock-beneficiary-gym", "Virgin Active", "62000000002"),
+            ],
+            pending: HashMap::from([(CHEQUE_ACCOUNT_ID.to_string(), vec![card_hold])]),
+            balances,
+            cards: vec![json!({
+                "CardKey": CARD_KEY,
+                "CardNumber": "402167XXXXXX1234",
+                "IsProgrammable": true,
+                "Status": "Active",
+                "CardTypeCode": "VGC",
+                "AccountNumber": "10010206147",
+                "AccountId": CHEQUE_ACCOUNT_ID,
+            })],
+        }
+    }
+}
+
+fn account(account_id: &str, account_number: &str, product_name: &str) -> Value {
+    json!({
+        "accountId": account_id,
+        "accountNumber": account_number,
+        "accountName": "Mr J Soap",
+        "referenceName": product_name,
+        "productName": product_name,
+        "kycCompliant": true,
+        "profileId": "mock-profile",
+        "profileName": "Mr J Soap",
+    })
+}
+
+fn beneficiary(beneficiary_id: &str, name: &str, account_number: &str) -> Value {
+    json!({
+        "beneficiaryId": beneficiary_id,
+        "accountNumber": account_number,
+        "code": "250655",
+        "bank": "FNB",
+        "beneficiaryName": name,
+        "lastPaymentAmount": "0.00",
+        "lastPaymentDate": null,
+        "cellNo": null,
+        "emailAddress": null,
+        "name": name,
+        "referenceAccountNumber": null,
+        "referenceName": name,
+        "categoryId": "mock-category",
+        "profileId": "mock-profile",
+    })
+}
+
+fn balance(account_id: &str, current: f64, available: f64) -> Value {
+    json!({
+        "accountId": account_id,
+        "currentBalance": current,
+        "availableBalance": available,
+        "currency": "ZAR",
+        "budgetBalance": 0.0,
+        "straightBalance": 0.0,
+        "cashBalance": current,
+    })
+}
+
+fn transaction(
+    account_id: &str,
+    date: NaiveDate,
+    tx_type: &str,
+    description: &str,
+    amount: f64,
+    transaction_type: Option<&str>,
+) -> Value {
+    let date = date.format("%Y-%m-%d").to_string();
+
+    json!({
+        "accountId": account_id,
+        "type": tx_type,
+        "transactionType": transaction_type,
+        "status": "POSTED",
+        "description": description,
+        "cardNumber": transaction_type
+            .filter(|kind| *kind == "CardPurchases")
+            .map(|_| "402167xxxxxx9999"),
+        "postedOrder": 1,
+        "postingDate": date,
+        "valueDate": date,
+        "actionDate": date,
+        "transactionDate": date,
+        "amount": amount,
+        "runningBalance": 0.0,
+        "uuid": format!("mock-{}-{}-{}", account_id, date, slug(description)),
+    })
+}
+
+fn slug(description: &str) -> String {
+    description
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
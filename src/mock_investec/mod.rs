@@ -0,0 +1,719 @@
+//! Local stand-in for the Investec Open API, used by tests and for offline
+//! development (`mock-investec` command with `INVESTEC_BASE_URL` pointed at it).
+
+pub mod fixtures;
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use axum::extract::{Form, Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use fixtures::Fixtures;
+
+const TOKEN_LIFETIME_SECS: u64 = 1799;
+
+/// What Investec puts on a new programmable card.
+const DEFAULT_CARD_CODE: &str = "const beforeTransaction = async (authorization) => true;\n";
+
+/// Saved and published code of one card.
+struct MockCard {
+    saved: Value,
+    published: Value,
+    programmable: bool,
+    variables: Value,
+}
+
+pub struct MockState {
+    fixtures: Fixtures,
+    /// Issued access tokens and the scopes granted to each.
+    tokens: Mutex<HashMap<String, String>>,
+    payments: Mutex<Vec<Value>>,
+    next_token: AtomicU64,
+    failures: Mutex<VecDeque<(StatusCode, Option<u64>)>>,
+    /// Accounts whose transactions always fail with the given status.
+    failing_accounts: Mutex<HashMap<String, StatusCode>>,
+    cards: Mutex<HashMap<String, MockCard>>,
+    page_size: usize,
+}
+
+impl MockState {
+    pub fn new(fixtures: Fixtures) -> Self {
+        let cards = fixtures
+            .cards
+            .iter()
+            .filter_map(|card| card["CardKey"].as_str())
+            .map(|card_key| {
+                let code = card_code(1, DEFAULT_CARD_CODE);
+                let card = MockCard {
+                    saved: code.clone(),
+                    published: code,
+                    programmable: true,
+                    variables: json!({}),
+                };
+                (card_key.to_string(), card)
+            })
+            .collect();
+
+        Self {
+            fixtures,
+            tokens: Mutex::new(HashMap::new()),
+            payments: Mutex::new(Vec::new()),
+            next_token: AtomicU64::new(1),
+            failures: Mutex::new(VecDeque::new()),
+            failing_accounts: Mutex::new(HashMap::new()),
+            cards: Mutex::new(cards),
+            page_size: 100,
+        }
+    }
+
+    /// Sets how many transactions are returned per page.
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Queues a failure to return from the next API call, with an optional
+    /// `Retry-After` in seconds.
+    #[cfg(test)]
+    pub fn inject_failure(&self, status: StatusCode, retry_after: Option<u64>) {
+        self.failures
+            .lock()
+            .unwrap()
+            .push_back((status, retry_after));
+    }
+
+    /// Fails every transactions request for `account_id` with `status`.
+    #[cfg(test)]
+    pub fn fail_account(&self, account_id: &str, status: StatusCode) {
+        self.failing_accounts
+            .lock()
+            .unwrap()
+            .insert(account_id.to_string(), status);
+    }
+
+    /// Invalidates every issued token, as if they had expired server-side.
+    #[cfg(test)]
+    pub fn revoke_tokens(&self) {
+        self.tokens.lock().unwrap().clear();
+    }
+
+    /// Transfers and payments accepted so far, in request order.
+    #[cfg(test)]
+    pub fn payments(&self) -> Vec<Value> {
+        self.payments.lock().unwrap().clone()
+    }
+
+    /// The scopes granted to the request's token, if it carries a valid one.
+    fn granted_scopes(&self, headers: &HeaderMap) -> Option<String> {
+        if !headers.contains_key("x-api-key") {
+            return None;
+        }
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))?;
+
+        self.tokens.lock().unwrap().get(token).cloned()
+    }
+
+    /// Returns the response to send instead of the real one, if the request is
+    /// unauthorized or a failure has been injected.
+    fn reject(&self, headers: &HeaderMap) -> Option<Response> {
+        self.reject_without_scope(headers, "accounts")
+    }
+
+    fn reject_without_scope(&self, headers: &HeaderMap, scope: &str) -> Option<Response> {
+        let Some(granted) = self.granted_scopes(headers) else {
+            return Some(error(StatusCode::UNAUTHORIZED, "invalid_token"));
+        };
+        if !granted.split_whitespace().any(|granted| granted == scope) {
+            return Some(error(StatusCode::FORBIDDEN, "insufficient_scope"));
+        }
+
+        let (status, retry_after) = self.failures.lock().unwrap().pop_front()?;
+        let mut response = error(status, "injected failure");
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, seconds.into());
+        }
+        Some(response)
+    }
+}
+
+pub fn router(state: Arc<MockState>) -> Router {
+    Router::new()
+        .route("/identity/v2/oauth2/token", post(token))
+        .route("/za/pb/v1/accounts", get(accounts))
+        .route("/za/pb/v1/accounts/beneficiaries", get(beneficiaries))
+        .route(
+            "/za/pb/v1/accounts/beneficiarycategories",
+            get(beneficiary_categories),
+        )
+        .route(
+            "/za/pb/v1/accounts/{account_id}/transfermultiple",
+            post(transfer_multiple),
+        )
+        .route(
+            "/za/pb/v1/accounts/{account_id}/paymultiple",
+            post(pay_multiple),
+        )
+        .route(
+            "/za/pb/v1/accounts/{account_id}/transactions",
+            get(transactions),
+        )
+        .route(
+            "/za/pb/v1/accounts/{account_id}/pending-transactions",
+            get(pending_transactions),
+        )
+        .route("/za/pb/v1/accounts/{account_id}/balance", get(balance))
+        .route("/za/v1/cards", get(cards))
+        .route(
+            "/za/v1/cards/{card_key}/code",
+            get(saved_code).post(save_code),
+        )
+        .route("/za/v1/cards/{card_key}/publishedcode", get(published_code))
+        .route("/za/v1/cards/{card_key}/publish", post(publish_code))
+        .route(
+            "/za/v1/cards/{card_key}/toggle-programmable-feature",
+            post(toggle_programmable_feature),
+        )
+        .route("/za/v1/cards/{card_key}/code/execute", post(execute_code))
+        .route(
+            "/za/v1/cards/{card_key}/environmentvariables",
+            get(environment_variables).post(save_environment_variables),
+        )
+        .with_state(state)
+}
+
+/// Binds `addr` and serves the mock in the background, returning the bound
+/// address (useful with port 0). The caller keeps `state` so tests can inject
+/// failures.
+pub async fn spawn(addr: &str, state: Arc<MockState>) -> Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+
+    let handle = tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router(state)).await {
+            tracing::error!(error = %e, "Mock Investec server stopped");
+        }
+    });
+
+    Ok((local_addr, handle))
+}
+
+pub async fn serve(addr: &str, page_size: usize) -> Result<()> {
+    let state = MockState::new(Fixtures::seed(Utc::now().date_naive())).with_page_size(page_size);
+    let (local_addr, handle) = spawn(addr, Arc::new(state)).await?;
+    tracing::info!("Mock Investec API listening on http://{}", local_addr);
+    handle.await?;
+    Ok(())
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+async fn token(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    if !headers.contains_key("x-api-key") {
+        return error(StatusCode::UNAUTHORIZED, "missing x-api-key");
+    }
+    if form.get("grant_type").map(String::as_str) != Some("client_credentials") {
+        return error(StatusCode::BAD_REQUEST, "unsupported_grant_type");
+    }
+    if form.get("client_id").is_none_or(|id| id.is_empty())
+        || form
+            .get("client_secret")
+            .is_none_or(|secret| secret.is_empty())
+    {
+        return error(StatusCode::UNAUTHORIZED, "invalid_client");
+    }
+
+    let token = format!(
+        "mock-token-{}",
+        state.next_token.fetch_add(1, Ordering::SeqCst)
+    );
+    let scope = form.get("scope").cloned().unwrap_or_default();
+    state
+        .tokens
+        .lock()
+        .unwrap()
+        .insert(token.clone(), scope.clone());
+
+    Json(json!({
+        "access_token": token,
+        "token_type": "Bearer",
+        "expires_in": TOKEN_LIFETIME_SECS,
+        "scope": scope,
+    }))
+    .into_response()
+}
+
+async fn accounts(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    if let Some(response) = state.reject(&headers) {
+        return response;
+    }
+
+    Json(json!({
+        "data": { "accounts": state.fixtures.accounts },
+        "links": { "self": "/za/pb/v1/accounts" },
+        "meta": { "totalPages": 1 },
+    }))
+    .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct TransactionsQuery {
+    #[serde(rename = "fromDate")]
+    from_date: Option<String>,
+    #[serde(rename = "toDate")]
+    to_date: Option<String>,
+    #[serde(rename = "transactionType")]
+    transaction_type: Option<String>,
+    #[serde(rename = "includePending", default)]
+    include_pending: bool,
+    page: Option<usize>,
+}
+
+async fn transactions(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(account_id): Path<String>,
+    Query(query): Query<TransactionsQuery>,
+) -> Response {
+    if let Some(response) = state.reject(&headers) {
+        return response;
+    }
+    if let Some(status) = state.failing_accounts.lock().unwrap().get(&account_id) {
+        return error(*status, "injected failure");
+    }
+    let Some(transactions) = state.fixtures.transactions.get(&account_id) else {
+        return error(StatusCode::NOT_FOUND, "account not found");
+    };
+
+    let pending = state
+        .fixtures
+        .pending
+        .get(&account_id)
+        .filter(|_| query.include_pending)
+        .into_iter()
+        .flatten();
+    let in_range: Vec<_> = transactions
+        .iter()
+        .chain(pending)
+        .filter(|tx| {
+            let date = tx["transactionDate"].as_str().unwrap_or_default();
+            query.from_date.as_deref().is_none_or(|from| date >= from)
+                && query.to_date.as_deref().is_none_or(|to| date <= to)
+                && query
+                    .transaction_type
+                    .as_deref()
+                    .is_none_or(|kind| tx["transactionType"].as_str() == Some(kind))
+        })
+        .collect();
+
+    let total_pages = in_range.len().div_ceil(state.page_size).max(1);
+    let page = query.page.unwrap_or(1).max(1);
+    let page_transactions: Vec<_> = in_range
+        .into_iter()
+        .skip((page - 1) * state.page_size)
+        .take(state.page_size)
+        .cloned()
+        .collect();
+
+    let path = format!("/za/pb/v1/accounts/{}/transactions", account_id);
+    let mut links = json!({ "self": format!("{}?page={}", path, page) });
+    if page < total_pages {
+        links["next"] = json!(format!("{}?page={}", path, page + 1));
+    }
+
+    Json(json!({
+        "data": { "transactions": page_transactions },
+        "links": links,
+        "meta": { "totalPages": total_pages },
+    }))
+    .into_response()
+}
+
+async fn pending_transactions(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(account_id): Path<String>,
+) -> Response {
+    if let Some(response) = state.reject(&headers) {
+        return response;
+    }
+    if !state.fixtures.transactions.contains_key(&account_id) {
+        return error(StatusCode::NOT_FOUND, "account not found");
+    }
+
+    let pending = state
+        .fixtures
+        .pending
+        .get(&account_id)
+        .cloned()
+        .unwrap_or_default();
+    Json(json!({ "data": { "transactions": pending } })).into_response()
+}
+
+async fn balance(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(account_id): Path<String>,
+) -> Response {
+    if let Some(response) = state.reject(&headers) {
+        return response;
+    }
+
+    match state.fixtures.balances.get(&account_id) {
+        Some(balance) => Json(json!({ "data": balance })).into_response(),
+        None => error(StatusCode::NOT_FOUND, "account not found"),
+    }
+}
+
+async fn beneficiaries(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    if let Some(response) = state.reject(&headers) {
+        return response;
+    }
+
+    Json(json!({ "data": state.fixtures.beneficiaries })).into_response()
+}
+
+async fn beneficiary_categories(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(response) = state.reject(&headers) {
+        return response;
+    }
+
+    Json(json!({
+        "data": [{ "id": "mock-category", "isDefault": "true", "name": "General" }],
+    }))
+    .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct TransferMultipleRequest {
+    #[serde(rename = "transferList")]
+    transfer_list: Vec<Value>,
+}
+
+async fn transfer_multiple(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(account_id): Path<String>,
+    Json(request): Json<TransferMultipleRequest>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "transfers") {
+        return response;
+    }
+    if !state.fixtures.balances.contains_key(&account_id) {
+        return error(StatusCode::NOT_FOUND, "account not found");
+    }
+
+    let to_account = |transfer: &Value| {
+        transfer["beneficiaryAccountId"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string()
+    };
+    if let Some(unknown) = request
+        .transfer_list
+        .iter()
+        .map(to_account)
+        .find(|to| !state.fixtures.balances.contains_key(to))
+    {
+        return payment_error(&format!("Unknown beneficiary account {}", unknown));
+    }
+
+    let results: Vec<_> = request
+        .transfer_list
+        .iter()
+        .map(|transfer| payment_result(&state, &account_id, &to_account(transfer), transfer))
+        .collect();
+
+    Json(json!({ "data": { "TransferResponses": results, "ErrorMessage": null } })).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct PayMultipleRequest {
+    #[serde(rename = "paymentList")]
+    payment_list: Vec<Value>,
+}
+
+async fn pay_multiple(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(account_id): Path<String>,
+    Json(request): Json<PayMultipleRequest>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "beneficiarypayments") {
+        return response;
+    }
+    if !state.fixtures.balances.contains_key(&account_id) {
+        return error(StatusCode::NOT_FOUND, "account not found");
+    }
+
+    let beneficiary_id = |payment: &Value| {
+        payment["beneficiaryId"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string()
+    };
+    if let Some(unknown) = request.payment_list.iter().map(beneficiary_id).find(|id| {
+        !state
+            .fixtures
+            .beneficiaries
+            .iter()
+            .any(|beneficiary| beneficiary["beneficiaryId"] == id.as_str())
+    }) {
+        return payment_error(&format!("Unknown beneficiary {}", unknown));
+    }
+
+    let results: Vec<_> = request
+        .payment_list
+        .iter()
+        .map(|payment| payment_result(&state, &account_id, &beneficiary_id(payment), payment))
+        .collect();
+
+    Json(json!({ "data": { "TransferResponses": results, "ErrorMessage": null } })).into_response()
+}
+
+fn payment_result(state: &MockState, from_account: &str, to: &str, instruction: &Value) -> Value {
+    let mut payments = state.payments.lock().unwrap();
+    let reference = format!("MOCK{:06}", payments.len() + 1);
+    payments.push(json!({ "from": from_account, "instruction": instruction }));
+
+    json!({
+        "PaymentReferenceNumber": reference,
+        "PaymentDate": Utc::now().format("%m/%d/%Y").to_string(),
+        "Status": "- No authorisation necessary <BR> - Payment/Transfer effective date 2024-01-01",
+        "BeneficiaryName": to,
+        "BeneficiaryAccountId": to,
+        "AuthorisationRequired": false,
+    })
+}
+
+/// Investec reports business-rule failures with a 200 and an `ErrorMessage`.
+fn payment_error(message: &str) -> Response {
+    Json(json!({ "data": { "TransferResponses": [], "ErrorMessage": message } })).into_response()
+}
+
+fn card_code(version: u64, code: &str) -> Value {
+    json!({
+        "codeId": format!("mock-code-{}", version),
+        "code": code,
+        "createdAt": Utc::now().to_rfc3339(),
+        "updatedAt": Utc::now().to_rfc3339(),
+        "error": null,
+    })
+}
+
+fn card_result(result: Value) -> Response {
+    Json(json!({ "data": { "result": result } })).into_response()
+}
+
+/// Runs `update` against the card's state, or returns a 404.
+fn with_card(
+    state: &MockState,
+    card_key: &str,
+    update: impl FnOnce(&mut MockCard) -> Response,
+) -> Response {
+    match state.cards.lock().unwrap().get_mut(card_key) {
+        Some(card) => update(card),
+        None => error(StatusCode::NOT_FOUND, "card not found"),
+    }
+}
+
+async fn cards(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "cards") {
+        return response;
+    }
+
+    Json(json!({ "data": { "cards": state.fixtures.cards } })).into_response()
+}
+
+async fn saved_code(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(card_key): Path<String>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "cards") {
+        return response;
+    }
+
+    with_card(&state, &card_key, |card| card_result(card.saved.clone()))
+}
+
+async fn published_code(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(card_key): Path<String>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "cards") {
+        return response;
+    }
+
+    with_card(&state, &card_key, |card| {
+        card_result(card.published.clone())
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct SaveCodeRequest {
+    code: String,
+}
+
+async fn save_code(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(card_key): Path<String>,
+    Json(request): Json<SaveCodeRequest>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "cards") {
+        return response;
+    }
+
+    let version = state.next_token.fetch_add(1, Ordering::SeqCst);
+    with_card(&state, &card_key, |card| {
+        card.saved = card_code(version, &request.code);
+        card_result(card.saved.clone())
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct PublishCodeRequest {
+    #[serde(rename = "codeid")]
+    code_id: String,
+    code: String,
+}
+
+async fn publish_code(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(card_key): Path<String>,
+    Json(request): Json<PublishCodeRequest>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "cards") {
+        return response;
+    }
+
+    with_card(&state, &card_key, |card| {
+        if card.saved["codeId"] != request.code_id.as_str() {
+            return error(StatusCode::BAD_REQUEST, "codeid does not match saved code");
+        }
+        card.saved["code"] = json!(request.code);
+        card.published = card.saved.clone();
+        card_result(card.published.clone())
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct ToggleRequest {
+    #[serde(rename = "Enabled")]
+    enabled: bool,
+}
+
+async fn toggle_programmable_feature(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(card_key): Path<String>,
+    Json(request): Json<ToggleRequest>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "cards") {
+        return response;
+    }
+
+    with_card(&state, &card_key, |card| {
+        card.programmable = request.enabled;
+        card_result(json!({ "Enabled": card.programmable }))
+    })
+}
+
+async fn environment_variables(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(card_key): Path<String>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "cards") {
+        return response;
+    }
+
+    with_card(&state, &card_key, |card| {
+        card_result(json!({ "variables": card.variables }))
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct EnvironmentRequest {
+    variables: Value,
+}
+
+async fn save_environment_variables(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(card_key): Path<String>,
+    Json(request): Json<EnvironmentRequest>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "cards") {
+        return response;
+    }
+
+    with_card(&state, &card_key, |card| {
+        card.variables = request.variables;
+        card_result(json!({ "variables": card.variables }))
+    })
+}
+
+/// The mock can't run JavaScript, so every simulation is approved.
+async fn execute_code(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Path(card_key): Path<String>,
+    Json(request): Json<Value>,
+) -> Response {
+    if let Some(response) = state.reject_without_scope(&headers, "cards") {
+        return response;
+    }
+
+    with_card(&state, &card_key, |_| {
+        let now = Utc::now().to_rfc3339();
+        card_result(json!([{
+            "executionId": "mock-execution",
+            "rootCodeFunctionId": "mock-function",
+            "sandbox": true,
+            "type": "before_transaction",
+            "authorizationApproved": true,
+            "logs": [{
+                "createdAt": now,
+                "level": "info",
+                "content": format!(
+                    "mock approved {} cents at {}",
+                    request["centsAmount"].as_str().unwrap_or_default(),
+                    request["merchantName"].as_str().unwrap_or_default()
+                ),
+            }],
+            "smsCount": 0,
+            "emailCount": 0,
+            "startedAt": now,
+            "completedAt": now,
+        }]))
+    })
+}
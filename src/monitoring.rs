@@ -0,0 +1,216 @@
+//! Prometheus metrics.
+//!
+//! Metrics are recorded through the `metrics` facade wherever the work
+//! happens and rendered on `/metrics` by the recorder installed in `run`.
+//! Without a recorder (one-off commands) recording is a no-op.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use axum::Router;
+use axum::extract::State;
+use axum::routing::get;
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use sqlx::PgPool;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::shutdown::Shutdown;
+
+/// Histogram buckets in seconds, from a single API call to a long sync.
+const DURATION_BUCKETS: &[f64] = &[
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+pub fn install() -> Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets(DURATION_BUCKETS)?
+        .install_recorder()?;
+
+    Ok(handle)
+}
+
+/// `result` is `success`, `failure` or `skipped`.
+pub fn sync_finished(result: &'static str, elapsed: Duration) {
+    counter!("itb_sync_runs_total", "result" => result).increment(1);
+    if result == "skipped" {
+        return;
+    }
+
+    histogram!("itb_sync_duration_seconds").record(elapsed.as_secs_f64());
+    if result == "success" {
+        gauge!("itb_sync_last_success_timestamp_seconds").set(unix_now());
+    }
+}
+
+pub fn sync_account_failed(profile: &str, account_id: &str) {
+    counter!(
+        "itb_sync_account_failures_total",
+        "profile" => profile.to_string(),
+        "account_id" => account_id.to_string()
+    )
+    .increment(1);
+}
+
+/// `status` is the HTTP status code, or `error` when no response arrived.
+pub fn investec_request(endpoint: &str, status: &str, elapsed: Duration) {
+    let labels = [
+        ("endpoint", endpoint.to_string()),
+        ("status", status.to_string()),
+    ];
+    counter!("itb_investec_requests_total", &labels).increment(1);
+    histogram!("itb_investec_request_duration_seconds", &labels).record(elapsed.as_secs_f64());
+}
+
+pub fn investec_token_refresh(success: bool) {
+    counter!("itb_investec_token_refreshes_total", "result" => outcome(success)).increment(1);
+}
+
+pub fn classifier_attempt(strategy: &'static str, success: bool, elapsed: Duration) {
+    counter!(
+        "itb_classifier_requests_total",
+        "strategy" => strategy,
+        "result" => outcome(success)
+    )
+    .increment(1);
+    histogram!("itb_classifier_duration_seconds", "strategy" => strategy)
+        .record(elapsed.as_secs_f64());
+}
+
+/// Every strategy failed and the transaction went to "Other".
+pub fn classifier_fallback() {
+    counter!("itb_classifier_fallbacks_total").increment(1);
+}
+
+/// `source` is `sync` or `card_event`.
+pub fn transaction_inserted(source: &'static str) {
+    counter!("itb_db_transactions_inserted_total", "source" => source).increment(1);
+}
+
+/// Spend per bucket for the current month. Every bucket in `buckets` is set,
+/// to zero when it has no spend, so last month's totals don't linger after
+/// the month rolls over.
+pub fn set_bucket_spend(buckets: &[String], spend: &[(String, f64)]) {
+    for bucket in buckets {
+        if !spend.iter().any(|(spent, _)| spent == bucket) {
+            gauge!("itb_bucket_spend", "bucket" => bucket.clone()).set(0.0);
+        }
+    }
+    for (bucket, amount) in spend {
+        gauge!("itb_bucket_spend", "bucket" => bucket.clone()).set(*amount);
+    }
+}
+
+/// Rewrites ids in an API path so requests group by endpoint, e.g.
+/// `za/pb/v1/accounts/123/transactions` becomes
+/// `za/pb/v1/accounts/:id/transactions`.
+pub fn endpoint_label(path: &str) -> String {
+    path.trim_start_matches('/')
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with(|c: char| c.is_ascii_digit()) {
+                ":id"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn outcome(success: bool) -> &'static str {
+    if success { "success" } else { "failure" }
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs_f64())
+        .unwrap_or_default()
+}
+
+struct MetricsState {
+    handle: PrometheusHandle,
+    pool: PgPool,
+}
+
+pub fn router(handle: PrometheusHandle, pool: PgPool) -> Router {
+    Router::new()
+        .route("/metrics", get(render))
+        .with_state(Arc::new(MetricsState { handle, pool }))
+}
+
+pub async fn spawn(
+    addr: &str,
+    handle: PrometheusHandle,
+    pool: PgPool,
+    shutdown: Arc<Shutdown>,
+) -> Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+
+    let handle = tokio::spawn(async move {
+        let server = axum::serve(listener, router(handle, pool))
+            .with_graceful_shutdown(async move { shutdown.requested().await });
+        if let Err(e) = server.await {
+            tracing::error!(error = %e, "Metrics server stopped");
+        }
+    });
+
+    Ok((local_addr, handle))
+}
+
+async fn render(State(state): State<Arc<MetricsState>>) -> String {
+    let idle = state.pool.num_idle() as f64;
+    gauge!("itb_db_pool_connections", "state" => "idle").set(idle);
+    gauge!("itb_db_pool_connections", "state" => "active").set(f64::from(state.pool.size()) - idle);
+
+    state.handle.run_upkeep();
+    state.handle.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_label() {
+        assert_eq!(
+            endpoint_label("/za/pb/v1/accounts/4675778129910189600000003/transactions"),
+            "za/pb/v1/accounts/:id/transactions"
+        );
+        assert_eq!(
+            endpoint_label("/identity/v2/oauth2/token"),
+            "identity/v2/oauth2/token"
+        );
+        assert_eq!(
+            endpoint_label("/za/v1/cards/1234/code"),
+            "za/v1/cards/:id/code"
+        );
+    }
+
+    #[test]
+    fn test_bucket_spend_resets_when_month_rolls_over() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let buckets = vec!["Food".to_string(), "Transport".to_string()];
+
+        metrics::with_local_recorder(&recorder, || {
+            set_bucket_spend(&buckets, &[("Food".to_string(), 1250.5)]);
+            assert!(
+                handle
+                    .render()
+                    .contains(r#"itb_bucket_spend{bucket="Food"} 1250.5"#)
+            );
+
+            set_bucket_spend(&buckets, &[]);
+        });
+
+        let rendered = handle.render();
+        assert!(rendered.contains(r#"itb_bucket_spend{bucket="Food"} 0"#));
+        assert!(rendered.contains(r#"itb_bucket_spend{bucket="Transport"} 0"#));
+    }
+}
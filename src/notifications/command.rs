@@ -0,0 +1,63 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::{Event, Sink};
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs a local shell command per event. The event is written to stdin as
+/// JSON and its kind, title and message are exposed as environment variables.
+#[derive(Debug)]
+pub struct CommandSink {
+    command: String,
+}
+
+impl CommandSink {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+}
+
+#[async_trait]
+impl Sink for CommandSink {
+    fn name(&self) -> &str {
+        "command"
+    }
+
+    async fn send(&self, event: &Event) -> Result<()> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env("NOTIFY_EVENT", event.kind.as_str())
+            .env("NOTIFY_TITLE", &event.title)
+            .env("NOTIFY_MESSAGE", &event.message)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&serde_json::to_vec(event)?).await?;
+        }
+
+        let output = tokio::time::timeout(COMMAND_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| anyhow::anyhow!("Notification command timed out"))??;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Notification command exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(())
+    }
+}
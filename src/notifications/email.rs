@@ -0,0 +1,167 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::template::{self, no_escape};
+use super::{Event, Sink};
+use crate::config::settings::NotificationsConfig;
+
+const DEFAULT_SUBJECT_TEMPLATE: &str = "[Investec buckets] {{title}}";
+const DEFAULT_BODY_TEMPLATE: &str = "{{message}}\n\n{{occurred_at}}";
+
+pub struct EmailSink {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    subject_template: String,
+    body_template: String,
+}
+
+impl EmailSink {
+    pub fn from_config(host: &str, config: &NotificationsConfig) -> Result<Self> {
+        let mut builder = match config.smtp_security.as_deref().unwrap_or("starttls") {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            other => return Err(anyhow::anyhow!("Unknown SMTP security mode: {}", other)),
+        };
+
+        if let Some(port) = config.smtp_port {
+            builder = builder.port(port);
+        }
+
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                password.expose().to_string(),
+            ));
+        }
+
+        let from = config
+            .email_from
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("NOTIFY_EMAIL_FROM is required for email"))?
+            .parse()?;
+
+        let to = config
+            .email_to
+            .iter()
+            .map(|address| address.parse())
+            .collect::<Result<Vec<Mailbox>, _>>()?;
+        if to.is_empty() {
+            return Err(anyhow::anyhow!("NOTIFY_EMAIL_TO is required for email"));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+            to,
+            subject_template: config
+                .email_subject_template
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SUBJECT_TEMPLATE.to_string()),
+            body_template: config
+                .email_body_template
+                .clone()
+                .unwrap_or_else(|| DEFAULT_BODY_TEMPLATE.to_string()),
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for EmailSink {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn send(&self, event: &Event) -> Result<()> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(template::render(&self.subject_template, event, no_escape))
+            .header(ContentType::TEXT_PLAIN);
+
+        for recipient in &self.to {
+            builder = builder.to(recipient.clone());
+        }
+
+        let message = builder.body(template::render(&self.body_template, event, no_escape))?;
+        self.transport.send(message).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Minimal SMTP server that accepts one message and returns its DATA.
+    async fn receive_one_message(listener: TcpListener) -> String {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = socket.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut data = String::new();
+        let mut in_data = false;
+
+        writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if in_data {
+                if line == "." {
+                    in_data = false;
+                    writer.write_all(b"250 OK\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                continue;
+            }
+
+            let command = line.to_uppercase();
+            if command.starts_with("EHLO") || command.starts_with("HELO") {
+                writer.write_all(b"250 localhost\r\n").await.unwrap();
+            } else if command.starts_with("DATA") {
+                in_data = true;
+                writer.write_all(b"354 Go ahead\r\n").await.unwrap();
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                writer.write_all(b"250 OK\r\n").await.unwrap();
+            }
+        }
+
+        data
+    }
+
+    #[tokio::test]
+    async fn test_email_sink_sends_rendered_message() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(receive_one_message(listener));
+
+        let config = NotificationsConfig {
+            smtp_host: Some("127.0.0.1".to_string()),
+            smtp_port: Some(port),
+            smtp_security: Some("none".to_string()),
+            email_from: Some("buckets@example.com".to_string()),
+            email_to: vec!["me@example.com".to_string()],
+            ..NotificationsConfig::default()
+        };
+
+        let sink = EmailSink::from_config("127.0.0.1", &config).unwrap();
+        sink.send(&Event::sync_failed("accounts", "boom"))
+            .await
+            .unwrap();
+        drop(sink);
+
+        let data = server.await.unwrap();
+        assert!(data.contains("Subject: [Investec buckets] Transaction sync failed"));
+        assert!(data.contains("accounts: boom"));
+    }
+}
@@ -0,0 +1,451 @@
+pub mod command;
+pub mod email;
+pub mod template;
+pub mod webhook;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::anomaly::Anomaly;
+use crate::budgets::BudgetAlert;
+use crate::config::settings::NotificationsConfig;
+use crate::recurring::{SubscriptionFlag, SubscriptionFlagKind};
+use crate::reports::MonthlyReport;
+use crate::sweeps::{SweepPlan, SweepPlanItem};
+
+pub use command::CommandSink;
+pub use email::EmailSink;
+pub use webhook::WebhookSink;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    NewTransactions,
+    Anomaly,
+    BudgetThreshold,
+    SubscriptionAlert,
+    SweepPlanned,
+    MonthlyReport,
+    SyncFailed,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::NewTransactions => "new_transactions",
+            EventKind::Anomaly => "anomaly",
+            EventKind::BudgetThreshold => "budget_threshold",
+            EventKind::SubscriptionAlert => "subscription_alert",
+            EventKind::SweepPlanned => "sweep_planned",
+            EventKind::MonthlyReport => "monthly_report",
+            EventKind::SyncFailed => "sync_failed",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "new_transactions" => Ok(EventKind::NewTransactions),
+            "anomaly" => Ok(EventKind::Anomaly),
+            "budget_threshold" => Ok(EventKind::BudgetThreshold),
+            "subscription_alert" => Ok(EventKind::SubscriptionAlert),
+            "sweep_planned" => Ok(EventKind::SweepPlanned),
+            "monthly_report" => Ok(EventKind::MonthlyReport),
+            "sync_failed" => Ok(EventKind::SyncFailed),
+            other => Err(anyhow::anyhow!("Unknown notification event: {}", other)),
+        }
+    }
+}
+
+/// A transaction stored and bucketed during a sync.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassifiedTransaction {
+    pub id: i32,
+    pub uuid: Option<String>,
+    pub account_id: String,
+    pub description: String,
+    pub amount: f64,
+    pub bucket: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub kind: EventKind,
+    pub title: String,
+    pub message: String,
+    pub occurred_at: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+impl Event {
+    pub fn new_transactions(transactions: &[ClassifiedTransaction]) -> Self {
+        let lines: Vec<String> = transactions
+            .iter()
+            .map(|tx| format!("{} {:.2} -> {}", tx.description, tx.amount, tx.bucket))
+            .collect();
+
+        Self {
+            kind: EventKind::NewTransactions,
+            title: format!("{} new transaction(s) classified", transactions.len()),
+            message: lines.join("\n"),
+            occurred_at: Utc::now(),
+            data: json!({
+                "count": transactions.len(),
+                "transactions": transactions,
+            }),
+        }
+    }
+
+    pub fn anomaly(anomaly: &Anomaly) -> Self {
+        let tx = &anomaly.transaction;
+        let kinds: Vec<&str> = anomaly.kinds.iter().map(|kind| kind.as_str()).collect();
+
+        Self {
+            kind: EventKind::Anomaly,
+            title: format!("Suspicious transaction: {}", tx.description),
+            message: format!(
+                "{} {:.2} on account {} flagged as {}",
+                tx.description,
+                tx.amount,
+                tx.account_id,
+                kinds.join(", ")
+            ),
+            occurred_at: Utc::now(),
+            data: json!({
+                "transaction": tx,
+                "anomalies": kinds,
+            }),
+        }
+    }
+
+    pub fn budget_threshold(alert: &BudgetAlert) -> Self {
+        Self {
+            kind: EventKind::BudgetThreshold,
+            title: format!("{} budget at {}%", alert.bucket, alert.threshold),
+            message: format!(
+                "Spent {:.2} of the {} {} budget of {:.2} since {}",
+                alert.spent, alert.period, alert.bucket, alert.limit, alert.period_start
+            ),
+            occurred_at: Utc::now(),
+            data: json!({
+                "bucket": alert.bucket,
+                "period": alert.period,
+                "period_start": alert.period_start,
+                "threshold": alert.threshold,
+                "spent": alert.spent,
+                "limit": alert.limit,
+            }),
+        }
+    }
+
+    pub fn subscription_alert(flag: &SubscriptionFlag) -> Self {
+        let (title, message) = match flag.kind {
+            SubscriptionFlagKind::Missed => (
+                format!("Subscription missed: {}", flag.description),
+                format!(
+                    "Expected a {} charge of {:.2} on {} but none arrived",
+                    flag.cadence, flag.expected_amount, flag.expected_date
+                ),
+            ),
+            SubscriptionFlagKind::Early => (
+                format!("Subscription charged early: {}", flag.description),
+                format!(
+                    "Charged {:.2} before the expected date {}",
+                    flag.actual_amount.unwrap_or(flag.expected_amount),
+                    flag.expected_date
+                ),
+            ),
+            SubscriptionFlagKind::PriceChanged => (
+                format!("Subscription price changed: {}", flag.description),
+                format!(
+                    "Charged {:.2} instead of the usual {:.2}",
+                    flag.actual_amount.unwrap_or(flag.expected_amount),
+                    flag.expected_amount
+                ),
+            ),
+        };
+
+        Self {
+            kind: EventKind::SubscriptionAlert,
+            title,
+            message,
+            occurred_at: Utc::now(),
+            data: json!({
+                "flag": flag.kind.as_str(),
+                "account_id": flag.account_id,
+                "description": flag.description,
+                "cadence": flag.cadence,
+                "expected_date": flag.expected_date,
+                "expected_amount": flag.expected_amount,
+                "actual_amount": flag.actual_amount,
+            }),
+        }
+    }
+
+    pub fn sweep_planned(plan: &SweepPlan, items: &[SweepPlanItem]) -> Self {
+        let total: f64 = items.iter().map(|item| item.amount).sum();
+        let lines: Vec<String> = items
+            .iter()
+            .map(|item| format!("{:.2} {}", item.amount, item.reason))
+            .collect();
+
+        Self {
+            kind: EventKind::SweepPlanned,
+            title: format!(
+                "Sweep plan {} for {} awaiting approval",
+                plan.id,
+                plan.period_start.format("%Y-%m")
+            ),
+            message: format!(
+                "{}\nTotal {:.2}. Approve with `sweep approve {}`.",
+                lines.join("\n"),
+                total,
+                plan.id
+            ),
+            occurred_at: Utc::now(),
+            data: json!({
+                "plan_id": plan.id,
+                "period_start": plan.period_start,
+                "period_end": plan.period_end,
+                "total": total,
+                "items": items
+                    .iter()
+                    .map(|item| json!({
+                        "from_account_id": item.from_account_id,
+                        "to_account_id": item.to_account_id,
+                        "amount": item.amount,
+                        "reason": item.reason,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+        }
+    }
+
+    /// `table` is the report rendered for reading, `data` carries the full report.
+    pub fn monthly_report(report: &MonthlyReport, table: &str) -> Self {
+        Self {
+            kind: EventKind::MonthlyReport,
+            title: format!("Spending report for {}", report.to_month),
+            message: table.to_string(),
+            occurred_at: Utc::now(),
+            data: json!({ "report": report }),
+        }
+    }
+
+    pub fn sync_failed(context: &str, error: &str) -> Self {
+        Self {
+            kind: EventKind::SyncFailed,
+            title: "Transaction sync failed".to_string(),
+            message: format!("{}: {}", context, error),
+            occurred_at: Utc::now(),
+            data: json!({
+                "context": context,
+                "error": error,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+pub trait Sink: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn send(&self, event: &Event) -> Result<()>;
+}
+
+struct Route {
+    sink: Box<dyn Sink>,
+    /// Event kinds delivered to the sink; `None` delivers everything.
+    kinds: Option<HashSet<EventKind>>,
+}
+
+impl Route {
+    fn accepts(&self, kind: EventKind) -> bool {
+        self.kinds
+            .as_ref()
+            .is_none_or(|kinds| kinds.contains(&kind))
+    }
+}
+
+pub struct Notifier {
+    routes: Vec<Route>,
+    retry_attempts: u32,
+    retry_delay: Duration,
+}
+
+impl fmt::Debug for Notifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sinks: Vec<&str> = self.routes.iter().map(|route| route.sink.name()).collect();
+        f.debug_struct("Notifier")
+            .field("sinks", &sinks)
+            .field("retry_attempts", &self.retry_attempts)
+            .finish()
+    }
+}
+
+impl Notifier {
+    pub fn new(retry_attempts: u32, retry_delay: Duration) -> Self {
+        Self {
+            routes: Vec::new(),
+            retry_attempts: retry_attempts.max(1),
+            retry_delay,
+        }
+    }
+
+    pub fn from_config(config: &NotificationsConfig) -> Result<Self> {
+        let mut notifier = Self::new(
+            config.retry_attempts,
+            Duration::from_millis(config.retry_delay_ms),
+        );
+
+        if let Some(url) = &config.webhook_url {
+            notifier.add_sink(
+                Box::new(WebhookSink::new(
+                    url.clone(),
+                    config.webhook_template.clone(),
+                )?),
+                parse_kinds(&config.webhook_events)?,
+            );
+        }
+
+        if let Some(host) = &config.smtp_host {
+            notifier.add_sink(
+                Box::new(EmailSink::from_config(host, config)?),
+                parse_kinds(&config.email_events)?,
+            );
+        }
+
+        if let Some(command) = &config.command {
+            notifier.add_sink(
+                Box::new(CommandSink::new(command.clone())),
+                parse_kinds(&config.command_events)?,
+            );
+        }
+
+        Ok(notifier)
+    }
+
+    pub fn add_sink(&mut self, sink: Box<dyn Sink>, kinds: Option<HashSet<EventKind>>) {
+        self.routes.push(Route { sink, kinds });
+    }
+
+    pub fn sink_names(&self) -> Vec<&str> {
+        self.routes.iter().map(|route| route.sink.name()).collect()
+    }
+
+    /// Delivers `event` to every sink routed for its kind. Failures are
+    /// retried and then logged; they never fail the caller.
+    pub async fn notify(&self, event: Event) {
+        for route in self.routes.iter().filter(|route| route.accepts(event.kind)) {
+            if let Err(e) = self.send_with_retry(route.sink.as_ref(), &event).await {
+                tracing::error!(
+                    sink = route.sink.name(),
+                    event = %event.kind,
+                    error = %e,
+                    "Failed to deliver notification"
+                );
+            }
+        }
+    }
+
+    async fn send_with_retry(&self, sink: &dyn Sink, event: &Event) -> Result<()> {
+        let mut delay = self.retry_delay;
+        let mut attempt = 1;
+
+        loop {
+            match sink.send(event).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= self.retry_attempts => return Err(e),
+                Err(e) => {
+                    tracing::warn!(
+                        sink = sink.name(),
+                        attempt,
+                        error = %e,
+                        "Notification delivery failed, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+fn parse_kinds(names: &[String]) -> Result<Option<HashSet<EventKind>>> {
+    if names.is_empty() {
+        return Ok(None);
+    }
+
+    names
+        .iter()
+        .map(|name| EventKind::from_str(name))
+        .collect::<Result<HashSet<_>>>()
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct FlakySink {
+        failures_left: AtomicU32,
+        delivered: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl Sink for FlakySink {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn send(&self, _event: &Event) -> Result<()> {
+            if self.failures_left.load(Ordering::SeqCst) > 0 {
+                self.failures_left.fetch_sub(1, Ordering::SeqCst);
+                return Err(anyhow::anyhow!("temporary failure"));
+            }
+            self.delivered.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notify_retries_and_routes_by_kind() {
+        let delivered = Arc::new(AtomicU32::new(0));
+        let mut notifier = Notifier::new(3, Duration::from_millis(1));
+        notifier.add_sink(
+            Box::new(FlakySink {
+                failures_left: AtomicU32::new(2),
+                delivered: Arc::clone(&delivered),
+            }),
+            Some(HashSet::from([EventKind::SyncFailed])),
+        );
+
+        notifier.notify(Event::new_transactions(&[])).await;
+        assert_eq!(delivered.load(Ordering::SeqCst), 0);
+
+        notifier
+            .notify(Event::sync_failed("accounts", "boom"))
+            .await;
+        assert_eq!(delivered.load(Ordering::SeqCst), 1);
+    }
+}
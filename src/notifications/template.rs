@@ -0,0 +1,87 @@
+use super::Event;
+
+/// Renders `{{kind}}`, `{{title}}`, `{{message}}`, `{{occurred_at}}` and
+/// `{{data.<field>}}` placeholders. Every substituted value is passed through
+/// `escape`, so JSON templates can escape values while plain text ones don't.
+pub fn render(template: &str, event: &Event, escape: fn(&str) -> String) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+
+        match after_open.find("}}") {
+            Some(end) => {
+                let key = after_open[..end].trim();
+                match lookup(event, key) {
+                    Some(value) => output.push_str(&escape(&value)),
+                    None => output.push_str(&rest[start..start + end + 4]),
+                }
+                rest = &after_open[end + 2..];
+            }
+            None => {
+                output.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+pub fn no_escape(value: &str) -> String {
+    value.to_string()
+}
+
+/// Escapes a value for embedding inside a JSON string literal.
+pub fn json_escape(value: &str) -> String {
+    let quoted = serde_json::to_string(value).unwrap_or_default();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+fn lookup(event: &Event, key: &str) -> Option<String> {
+    match key {
+        "kind" => Some(event.kind.to_string()),
+        "title" => Some(event.title.clone()),
+        "message" => Some(event.message.clone()),
+        "occurred_at" => Some(event.occurred_at.to_rfc3339()),
+        _ => {
+            let field = key.strip_prefix("data.")?;
+            match event.data.get(field)? {
+                serde_json::Value::String(value) => Some(value.clone()),
+                value => Some(value.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_substitutes_placeholders() {
+        let event = Event::sync_failed("accounts", "timed out");
+
+        assert_eq!(
+            render(
+                "[{{kind}}] {{title}}: {{data.error}} {{unknown}}",
+                &event,
+                no_escape
+            ),
+            "[sync_failed] Transaction sync failed: timed out {{unknown}}"
+        );
+    }
+
+    #[test]
+    fn test_render_json_escapes_values() {
+        let event = Event::sync_failed("accounts", "bad \"quote\"\nline");
+
+        assert_eq!(
+            render(r#"{"text":"{{data.error}}"}"#, &event, json_escape),
+            r#"{"text":"bad \"quote\"\nline"}"#
+        );
+    }
+}
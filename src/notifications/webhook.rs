@@ -0,0 +1,116 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+
+use super::template::{self, json_escape};
+use super::{Event, Sink};
+
+/// Posts events as JSON. Without a template the serialized [`Event`] is sent;
+/// with one, the rendered template is sent as the body.
+#[derive(Debug)]
+pub struct WebhookSink {
+    client: Client,
+    url: String,
+    template: Option<String>,
+}
+
+impl WebhookSink {
+    pub fn new(url: String, template: Option<String>) -> Result<Self> {
+        Ok(Self {
+            client: Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()?,
+            url,
+            template,
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn send(&self, event: &Event) -> Result<()> {
+        let body = match &self.template {
+            Some(template) => template::render(template, event, json_escape),
+            None => serde_json::to_string(event)?,
+        };
+
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "Webhook returned status {}: {}",
+                status,
+                body
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Accepts one HTTP request, replies 200 and returns the raw request.
+    async fn serve_once(listener: TcpListener) -> String {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            let read = socket.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let content_length = text[..header_end]
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|value| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if request.len() >= header_end + 4 + content_length {
+                    break;
+                }
+            }
+        }
+
+        socket
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .await
+            .unwrap();
+        String::from_utf8(request).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_webhook_posts_rendered_template() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_once(listener));
+
+        let sink = WebhookSink::new(url, Some(r#"{"text":"{{title}}"}"#.to_string())).unwrap();
+        sink.send(&Event::sync_failed("accounts", "boom"))
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /hook"));
+        assert!(request.ends_with(r#"{"text":"Transaction sync failed"}"#));
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::{Days, Months, NaiveDate};
+use sqlx::PgPool;
+
+use crate::db;
+
+/// How far back stored transactions are analysed for recurring charges.
+const HISTORY_DAYS: u64 = 400;
+/// Relative difference between amounts still treated as the same charge.
+const AMOUNT_TOLERANCE: f64 = 0.2;
+/// Share of intervals that must match a cadence for it to be detected.
+const REGULAR_INTERVAL_RATIO: f64 = 0.75;
+/// Relative change from the expected amount that is reported as a price change.
+const PRICE_CHANGE_THRESHOLD: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cadence {
+    Weekly,
+    Monthly,
+    Annual,
+}
+
+impl Cadence {
+    const ALL: [Cadence; 3] = [Cadence::Weekly, Cadence::Monthly, Cadence::Annual];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Cadence::Weekly => "weekly",
+            Cadence::Monthly => "monthly",
+            Cadence::Annual => "annual",
+        }
+    }
+
+    fn interval_days(&self) -> i64 {
+        match self {
+            Cadence::Weekly => 7,
+            Cadence::Monthly => 30,
+            Cadence::Annual => 365,
+        }
+    }
+
+    /// Days an occurrence may drift from the expected date.
+    pub fn tolerance_days(&self) -> i64 {
+        match self {
+            Cadence::Weekly => 2,
+            Cadence::Monthly => 5,
+            Cadence::Annual => 15,
+        }
+    }
+
+    fn min_occurrences(&self) -> usize {
+        match self {
+            Cadence::Weekly | Cadence::Monthly => 3,
+            Cadence::Annual => 2,
+        }
+    }
+
+    pub fn next_after(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Cadence::Weekly => date + Days::new(7),
+            Cadence::Monthly => date + Months::new(1),
+            Cadence::Annual => date + Months::new(12),
+        }
+    }
+}
+
+impl fmt::Display for Cadence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Cadence {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Cadence::ALL
+            .into_iter()
+            .find(|cadence| cadence.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown cadence: {}", s))
+    }
+}
+
+/// A stored debit used as input to detection.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DebitHistoryRow {
+    pub account_id: String,
+    pub description: String,
+    pub date: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub date: NaiveDate,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedSubscription {
+    pub account_id: String,
+    pub merchant: String,
+    pub description: String,
+    pub cadence: Cadence,
+    pub expected_amount: f64,
+    pub occurrences: usize,
+    pub last_seen_date: NaiveDate,
+    pub next_expected_date: NaiveDate,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Subscription {
+    pub id: i32,
+    pub account_id: String,
+    pub merchant: String,
+    pub description: String,
+    pub cadence: String,
+    pub expected_amount: f64,
+    pub occurrences: i32,
+    pub last_seen_date: NaiveDate,
+    pub next_expected_date: NaiveDate,
+    pub status: String,
+}
+
+pub const STATUS_ACTIVE: &str = "active";
+pub const STATUS_MISSED: &str = "missed";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionFlagKind {
+    Missed,
+    Early,
+    PriceChanged,
+}
+
+impl SubscriptionFlagKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionFlagKind::Missed => "missed",
+            SubscriptionFlagKind::Early => "early",
+            SubscriptionFlagKind::PriceChanged => "price_changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionFlag {
+    pub kind: SubscriptionFlagKind,
+    pub account_id: String,
+    pub description: String,
+    pub cadence: String,
+    pub expected_date: NaiveDate,
+    pub expected_amount: f64,
+    /// The amount charged for early or price-changed occurrences.
+    pub actual_amount: Option<f64>,
+}
+
+/// Lowercases a transaction description and strips reference numbers and
+/// punctuation so repeat charges from one merchant group together.
+pub fn normalize_merchant(description: &str) -> String {
+    description
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty() && !token.chars().any(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Detects a regular cadence in `occurrences` (any order) for one merchant.
+pub fn detect_cadence(occurrences: &[Occurrence]) -> Option<(Cadence, f64)> {
+    let mut sorted = occurrences.to_vec();
+    sorted.sort_by_key(|occurrence| occurrence.date);
+    sorted.dedup_by_key(|occurrence| occurrence.date);
+
+    if sorted.len() < 2 {
+        return None;
+    }
+
+    // The latest charge is left out, so a price change still detects the
+    // subscription and is reported by `compare_occurrence`. Earlier charges
+    // may match the latest amount once the new price has repeated.
+    let (latest, history) = sorted.split_last()?;
+    let reference_amount = median(history.iter().map(|o| o.amount.abs()).collect())?;
+    let within_tolerance = |amount: f64, reference: f64| {
+        reference > 0.0 && (amount - reference).abs() / reference <= AMOUNT_TOLERANCE
+    };
+    let amounts_match = history.iter().all(|occurrence| {
+        within_tolerance(occurrence.amount.abs(), reference_amount)
+            || within_tolerance(occurrence.amount.abs(), latest.amount.abs())
+    });
+    if !amounts_match {
+        return None;
+    }
+
+    let intervals: Vec<i64> = sorted
+        .windows(2)
+        .map(|pair| (pair[1].date - pair[0].date).num_days())
+        .collect();
+
+    Cadence::ALL.into_iter().find_map(|cadence| {
+        let regular_intervals = intervals
+            .iter()
+            .filter(|days| (*days - cadence.interval_days()).abs() <= cadence.tolerance_days())
+            .count();
+        // Allow the odd skipped or shifted charge in a long history
+        let regular = regular_intervals as f64 >= intervals.len() as f64 * REGULAR_INTERVAL_RATIO;
+        (regular && sorted.len() >= cadence.min_occurrences())
+            .then(|| (cadence, latest.amount.abs()))
+    })
+}
+
+pub fn detect_subscriptions(rows: &[DebitHistoryRow]) -> Vec<DetectedSubscription> {
+    let mut groups: BTreeMap<(String, String), Vec<(&DebitHistoryRow, NaiveDate)>> =
+        BTreeMap::new();
+
+    for row in rows {
+        let merchant = normalize_merchant(&row.description);
+        let Some(date) = parse_date(&row.date) else {
+            continue;
+        };
+        if merchant.is_empty() {
+            continue;
+        }
+        groups
+            .entry((row.account_id.clone(), merchant))
+            .or_default()
+            .push((row, date));
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|((account_id, merchant), entries)| {
+            let occurrences: Vec<Occurrence> = entries
+                .iter()
+                .map(|(row, date)| Occurrence {
+                    date: *date,
+                    amount: row.amount,
+                })
+                .collect();
+            let (cadence, expected_amount) = detect_cadence(&occurrences)?;
+            let (latest, last_seen_date) = entries.iter().max_by_key(|(_, date)| *date)?;
+
+            Some(DetectedSubscription {
+                account_id,
+                merchant,
+                description: latest.description.clone(),
+                cadence,
+                expected_amount,
+                occurrences: occurrences.len(),
+                last_seen_date: *last_seen_date,
+                next_expected_date: cadence.next_after(*last_seen_date),
+            })
+        })
+        .collect()
+}
+
+/// Flags for a newly seen occurrence of an already known subscription.
+pub fn compare_occurrence(
+    stored: &Subscription,
+    detected: &DetectedSubscription,
+) -> Vec<SubscriptionFlagKind> {
+    let mut flags = Vec::new();
+    if detected.last_seen_date <= stored.last_seen_date {
+        return flags;
+    }
+
+    let tolerance = detected.cadence.tolerance_days();
+    if (stored.next_expected_date - detected.last_seen_date).num_days() > tolerance {
+        flags.push(SubscriptionFlagKind::Early);
+    }
+
+    if stored.expected_amount > 0.0
+        && (detected.expected_amount - stored.expected_amount).abs() / stored.expected_amount
+            > PRICE_CHANGE_THRESHOLD
+    {
+        flags.push(SubscriptionFlagKind::PriceChanged);
+    }
+
+    flags
+}
+
+pub fn is_overdue(next_expected_date: NaiveDate, cadence: Cadence, today: NaiveDate) -> bool {
+    (today - next_expected_date).num_days() > cadence.tolerance_days()
+}
+
+/// Re-detects subscriptions from stored transactions, persists them and
+/// returns missed, early and price-changed occurrences since the last run.
+pub async fn evaluate_subscriptions(
+    pool: &PgPool,
+    today: NaiveDate,
+) -> Result<Vec<SubscriptionFlag>> {
+    let since = today - Days::new(HISTORY_DAYS);
+    let history = db::fetch_debit_history(pool, since).await?;
+    let detected = detect_subscriptions(&history);
+
+    let stored: HashMap<(String, String), Subscription> = db::list_subscriptions(pool)
+        .await?
+        .into_iter()
+        .map(|sub| ((sub.account_id.clone(), sub.merchant.clone()), sub))
+        .collect();
+
+    let mut flags = Vec::new();
+
+    for subscription in &detected {
+        let key = (
+            subscription.account_id.clone(),
+            subscription.merchant.clone(),
+        );
+        let previous = stored.get(&key);
+
+        let status = match previous {
+            Some(previous) if subscription.last_seen_date <= previous.last_seen_date => {
+                previous.status.clone()
+            }
+            _ => STATUS_ACTIVE.to_string(),
+        };
+
+        if let Some(previous) = previous {
+            for kind in compare_occurrence(previous, subscription) {
+                flags.push(SubscriptionFlag {
+                    kind,
+                    account_id: subscription.account_id.clone(),
+                    description: subscription.description.clone(),
+                    cadence: subscription.cadence.to_string(),
+                    expected_date: previous.next_expected_date,
+                    expected_amount: previous.expected_amount,
+                    actual_amount: Some(subscription.expected_amount),
+                });
+            }
+        }
+
+        db::upsert_subscription(pool, subscription, &status).await?;
+    }
+
+    for subscription in db::list_subscriptions(pool).await? {
+        let cadence = Cadence::from_str(&subscription.cadence)?;
+        if subscription.status == STATUS_ACTIVE
+            && is_overdue(subscription.next_expected_date, cadence, today)
+        {
+            db::set_subscription_status(pool, subscription.id, STATUS_MISSED).await?;
+            flags.push(SubscriptionFlag {
+                kind: SubscriptionFlagKind::Missed,
+                account_id: subscription.account_id,
+                description: subscription.description,
+                cadence: subscription.cadence,
+                expected_date: subscription.next_expected_date,
+                expected_amount: subscription.expected_amount,
+                actual_amount: None,
+            });
+        }
+    }
+
+    Ok(flags)
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    Some(values[values.len() / 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn occurrence(date: NaiveDate, amount: f64) -> Occurrence {
+        Occurrence { date, amount }
+    }
+
+    #[test]
+    fn test_normalize_merchant_strips_references() {
+        assert_eq!(
+            normalize_merchant("NETFLIX.COM 866-579-7172 LOS GATOS"),
+            "netflix com los gatos"
+        );
+        assert_eq!(normalize_merchant("Spotify P1A2B3C4"), "spotify");
+    }
+
+    #[test]
+    fn test_detect_monthly_cadence() {
+        let occurrences = vec![
+            occurrence(date(2024, 1, 3), 199.0),
+            occurrence(date(2024, 2, 2), 199.0),
+            occurrence(date(2024, 3, 4), 219.0),
+        ];
+
+        assert_eq!(
+            detect_cadence(&occurrences),
+            Some((Cadence::Monthly, 219.0))
+        );
+    }
+
+    #[test]
+    fn test_detect_cadence_rejects_irregular_charges() {
+        let occurrences = vec![
+            occurrence(date(2024, 1, 3), 199.0),
+            occurrence(date(2024, 1, 20), 199.0),
+            occurrence(date(2024, 3, 4), 199.0),
+        ];
+
+        assert_eq!(detect_cadence(&occurrences), None);
+    }
+
+    #[test]
+    fn test_detect_cadence_keeps_a_price_rise() {
+        let mut occurrences = vec![
+            occurrence(date(2024, 1, 3), 199.0),
+            occurrence(date(2024, 2, 2), 199.0),
+            occurrence(date(2024, 3, 4), 199.0),
+            occurrence(date(2024, 4, 3), 249.0),
+        ];
+        assert_eq!(
+            detect_cadence(&occurrences),
+            Some((Cadence::Monthly, 249.0))
+        );
+
+        occurrences.push(occurrence(date(2024, 5, 3), 249.0));
+        assert_eq!(
+            detect_cadence(&occurrences),
+            Some((Cadence::Monthly, 249.0))
+        );
+    }
+
+    #[test]
+    fn test_price_rise_is_flagged() {
+        let row = |date: &str, amount: f64| DebitHistoryRow {
+            account_id: "acc".to_string(),
+            description: "NETFLIX".to_string(),
+            date: date.to_string(),
+            amount,
+        };
+        let stored = Subscription {
+            id: 1,
+            account_id: "acc".to_string(),
+            merchant: "netflix".to_string(),
+            description: "NETFLIX".to_string(),
+            cadence: "monthly".to_string(),
+            expected_amount: 199.0,
+            occurrences: 3,
+            last_seen_date: date(2024, 3, 4),
+            next_expected_date: date(2024, 4, 4),
+            status: STATUS_ACTIVE.to_string(),
+        };
+
+        let detected = detect_subscriptions(&[
+            row("2024-01-03", 199.0),
+            row("2024-02-02", 199.0),
+            row("2024-03-04", 199.0),
+            row("2024-04-03", 249.0),
+        ]);
+
+        assert_eq!(detected.len(), 1);
+        assert_eq!(
+            compare_occurrence(&stored, &detected[0]),
+            vec![SubscriptionFlagKind::PriceChanged]
+        );
+    }
+
+    #[test]
+    fn test_detect_cadence_rejects_mismatched_history() {
+        let occurrences = vec![
+            occurrence(date(2024, 1, 3), 199.0),
+            occurrence(date(2024, 2, 2), 499.0),
+            occurrence(date(2024, 3, 4), 199.0),
+            occurrence(date(2024, 4, 3), 199.0),
+        ];
+
+        assert_eq!(detect_cadence(&occurrences), None);
+    }
+
+    #[test]
+    fn test_compare_occurrence_flags_early_and_price_change() {
+        let stored = Subscription {
+            id: 1,
+            account_id: "acc".to_string(),
+            merchant: "netflix".to_string(),
+            description: "NETFLIX".to_string(),
+            cadence: "monthly".to_string(),
+            expected_amount: 199.0,
+            occurrences: 3,
+            last_seen_date: date(2024, 3, 1),
+            next_expected_date: date(2024, 4, 1),
+            status: STATUS_ACTIVE.to_string(),
+        };
+        let detected = DetectedSubscription {
+            account_id: "acc".to_string(),
+            merchant: "netflix".to_string(),
+            description: "NETFLIX".to_string(),
+            cadence: Cadence::Monthly,
+            expected_amount: 229.0,
+            occurrences: 4,
+            last_seen_date: date(2024, 3, 20),
+            next_expected_date: date(2024, 4, 20),
+        };
+
+        assert_eq!(
+            compare_occurrence(&stored, &detected),
+            vec![
+                SubscriptionFlagKind::Early,
+                SubscriptionFlagKind::PriceChanged
+            ]
+        );
+    }
+}
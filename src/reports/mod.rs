@@ -0,0 +1,32 @@
+pub mod monthly;
+pub mod render;
+
+pub use monthly::{MonthlyReport, ReportRow, build_monthly_report};
+pub use render::ReportFormat;
+
+use anyhow::Result;
+use sqlx::PgPool;
+
+use crate::db;
+
+/// Loads stored transactions for `from_month..=to_month` and builds the report.
+pub async fn generate_monthly_report(
+    pool: &PgPool,
+    from_month: &str,
+    to_month: &str,
+    account_id: Option<&str>,
+    profile: Option<&str>,
+    top_merchants: usize,
+) -> Result<MonthlyReport> {
+    monthly::parse_month(to_month)?;
+    let comparison_month = monthly::previous_month(from_month)?;
+    let rows =
+        db::fetch_report_rows(pool, &comparison_month, to_month, account_id, profile).await?;
+
+    Ok(build_monthly_report(
+        &rows,
+        from_month,
+        to_month,
+        top_merchants,
+    ))
+}
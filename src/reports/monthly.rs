@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{Months, NaiveDate};
+use serde::Serialize;
+
+pub const BUCKET_TRANSFERS: &str = "Transfers";
+
+const TX_TYPE_CREDIT: &str = "CREDIT";
+
+/// A single stored transaction joined with its bucket, as read for reporting.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ReportRow {
+    pub month: String,
+    pub account_id: String,
+    pub bucket: String,
+    pub tx_type: String,
+    pub description: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MerchantTotal {
+    pub merchant: String,
+    pub debits: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BucketSummary {
+    pub bucket: String,
+    pub debits: f64,
+    pub credits: f64,
+    pub net: f64,
+    /// Change in debits compared to the same bucket in the previous month.
+    pub debits_change: Option<f64>,
+    pub top_merchants: Vec<MerchantTotal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Totals {
+    pub debits: f64,
+    pub credits: f64,
+    pub net: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountSummary {
+    pub account_id: String,
+    pub buckets: Vec<BucketSummary>,
+    pub totals_excluding_transfers: Totals,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonthSummary {
+    pub month: String,
+    pub buckets: Vec<BucketSummary>,
+    pub totals_excluding_transfers: Totals,
+    pub accounts: Vec<AccountSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonthlyReport {
+    pub from_month: String,
+    pub to_month: String,
+    pub months: Vec<MonthSummary>,
+}
+
+/// Parses a `YYYY-MM` month into the first day of that month.
+pub fn parse_month(month: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("Invalid month '{}', expected YYYY-MM", month))
+}
+
+pub fn previous_month(month: &str) -> Result<String> {
+    let first = parse_month(month)?;
+    let previous = first
+        .checked_sub_months(Months::new(1))
+        .ok_or_else(|| anyhow::anyhow!("Month out of range: {}", month))?;
+    Ok(previous.format("%Y-%m").to_string())
+}
+
+/// Aggregates rows into per-month bucket summaries.
+///
+/// `rows` may include the month before `from_month`; it is only used to
+/// compute the change for the first reported month. A month without rows
+/// before it has no change.
+pub fn build_monthly_report(
+    rows: &[ReportRow],
+    from_month: &str,
+    to_month: &str,
+    top_merchants: usize,
+) -> MonthlyReport {
+    let mut by_month: BTreeMap<&str, Vec<&ReportRow>> = BTreeMap::new();
+    for row in rows {
+        by_month.entry(row.month.as_str()).or_default().push(row);
+    }
+
+    let mut months = Vec::new();
+    let mut all_by_month: BTreeMap<String, Vec<BucketSummary>> = BTreeMap::new();
+    let mut accounts_by_month: BTreeMap<String, BTreeMap<String, Vec<BucketSummary>>> =
+        BTreeMap::new();
+
+    for (month, month_rows) in by_month {
+        let previous = previous_month(month).ok();
+        let previous_all = previous
+            .as_ref()
+            .and_then(|previous| all_by_month.get(previous));
+        let previous_accounts = previous
+            .as_ref()
+            .and_then(|previous| accounts_by_month.get(previous));
+        let buckets =
+            summarize_buckets(&month_rows, previous_all.map(Vec::as_slice), top_merchants);
+
+        let mut by_account: BTreeMap<&str, Vec<&ReportRow>> = BTreeMap::new();
+        for row in &month_rows {
+            by_account
+                .entry(row.account_id.as_str())
+                .or_default()
+                .push(row);
+        }
+
+        let mut accounts = Vec::new();
+        let mut current_accounts = BTreeMap::new();
+        for (account_id, account_rows) in by_account {
+            let account_buckets = summarize_buckets(
+                &account_rows,
+                previous_accounts
+                    .and_then(|previous| previous.get(account_id))
+                    .map(|b| b.as_slice()),
+                top_merchants,
+            );
+            current_accounts.insert(account_id.to_string(), account_buckets.clone());
+            accounts.push(AccountSummary {
+                account_id: account_id.to_string(),
+                totals_excluding_transfers: totals_excluding_transfers(&account_buckets),
+                buckets: account_buckets,
+            });
+        }
+
+        if month >= from_month && month <= to_month {
+            months.push(MonthSummary {
+                month: month.to_string(),
+                totals_excluding_transfers: totals_excluding_transfers(&buckets),
+                buckets: buckets.clone(),
+                accounts,
+            });
+        }
+
+        all_by_month.insert(month.to_string(), buckets);
+        accounts_by_month.insert(month.to_string(), current_accounts);
+    }
+
+    MonthlyReport {
+        from_month: from_month.to_string(),
+        to_month: to_month.to_string(),
+        months,
+    }
+}
+
+fn summarize_buckets(
+    rows: &[&ReportRow],
+    previous: Option<&[BucketSummary]>,
+    top_merchants: usize,
+) -> Vec<BucketSummary> {
+    let mut by_bucket: BTreeMap<&str, Vec<&ReportRow>> = BTreeMap::new();
+    for row in rows {
+        by_bucket.entry(row.bucket.as_str()).or_default().push(row);
+    }
+
+    by_bucket
+        .into_iter()
+        .map(|(bucket, bucket_rows)| {
+            let mut debits = 0.0;
+            let mut credits = 0.0;
+            let mut merchants: BTreeMap<String, MerchantTotal> = BTreeMap::new();
+
+            for row in bucket_rows {
+                if row.tx_type.eq_ignore_ascii_case(TX_TYPE_CREDIT) {
+                    credits += row.amount.abs();
+                } else {
+                    debits += row.amount.abs();
+                    let merchant = row.description.trim().to_string();
+                    let entry = merchants
+                        .entry(merchant.to_lowercase())
+                        .or_insert(MerchantTotal {
+                            merchant,
+                            debits: 0.0,
+                            count: 0,
+                        });
+                    entry.debits += row.amount.abs();
+                    entry.count += 1;
+                }
+            }
+
+            let mut top: Vec<MerchantTotal> = merchants.into_values().collect();
+            top.sort_by(|a, b| b.debits.total_cmp(&a.debits));
+            top.truncate(top_merchants);
+
+            let debits_change = previous.map(|previous| {
+                let previous_debits = previous
+                    .iter()
+                    .find(|summary| summary.bucket == bucket)
+                    .map(|summary| summary.debits)
+                    .unwrap_or(0.0);
+                debits - previous_debits
+            });
+
+            BucketSummary {
+                bucket: bucket.to_string(),
+                debits,
+                credits,
+                net: credits - debits,
+                debits_change,
+                top_merchants: top,
+            }
+        })
+        .collect()
+}
+
+fn totals_excluding_transfers(buckets: &[BucketSummary]) -> Totals {
+    let (debits, credits) = buckets
+        .iter()
+        .filter(|summary| !summary.bucket.eq_ignore_ascii_case(BUCKET_TRANSFERS))
+        .fold((0.0, 0.0), |(debits, credits), summary| {
+            (debits + summary.debits, credits + summary.credits)
+        });
+
+    Totals {
+        debits,
+        credits,
+        net: credits - debits,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(month: &str, bucket: &str, tx_type: &str, description: &str, amount: f64) -> ReportRow {
+        ReportRow {
+            month: month.to_string(),
+            account_id: "acc-1".to_string(),
+            bucket: bucket.to_string(),
+            tx_type: tx_type.to_string(),
+            description: description.to_string(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_build_monthly_report_aggregates_buckets() {
+        let rows = vec![
+            row("2024-02", "Food", "DEBIT", "Woolworths", 200.0),
+            row("2024-02", "Food", "DEBIT", "Checkers", 50.0),
+            row("2024-02", "Food", "DEBIT", "Woolworths", 100.0),
+            row("2024-02", "Income", "CREDIT", "Salary", 1000.0),
+        ];
+
+        let report = build_monthly_report(&rows, "2024-02", "2024-02", 1);
+        let month = &report.months[0];
+        let food = month.buckets.iter().find(|b| b.bucket == "Food").unwrap();
+
+        assert_eq!(food.debits, 350.0);
+        assert_eq!(food.top_merchants.len(), 1);
+        assert_eq!(food.top_merchants[0].merchant, "Woolworths");
+        assert_eq!(food.top_merchants[0].count, 2);
+        assert_eq!(month.totals_excluding_transfers.net, 650.0);
+    }
+
+    #[test]
+    fn test_build_monthly_report_month_over_month_change() {
+        let rows = vec![
+            row("2024-01", "Food", "DEBIT", "Woolworths", 300.0),
+            row("2024-02", "Food", "DEBIT", "Woolworths", 200.0),
+        ];
+
+        let report = build_monthly_report(&rows, "2024-02", "2024-02", 3);
+
+        assert_eq!(report.months.len(), 1);
+        assert_eq!(report.months[0].buckets[0].debits_change, Some(-100.0));
+    }
+
+    #[test]
+    fn test_build_monthly_report_skips_change_after_gap_month() {
+        let rows = vec![
+            row("2024-01", "Food", "DEBIT", "Woolworths", 300.0),
+            row("2024-03", "Food", "DEBIT", "Woolworths", 200.0),
+            row("2024-04", "Food", "DEBIT", "Woolworths", 250.0),
+        ];
+
+        let report = build_monthly_report(&rows, "2024-01", "2024-04", 3);
+        let changes: Vec<_> = report
+            .months
+            .iter()
+            .map(|month| (month.month.as_str(), month.buckets[0].debits_change))
+            .collect();
+
+        assert_eq!(
+            changes,
+            [
+                ("2024-01", None),
+                ("2024-03", None),
+                ("2024-04", Some(50.0))
+            ]
+        );
+        assert_eq!(report.months[1].accounts[0].buckets[0].debits_change, None);
+    }
+
+    #[test]
+    fn test_totals_exclude_transfers() {
+        let rows = vec![
+            row("2024-02", "Food", "DEBIT", "Woolworths", 100.0),
+            row("2024-02", "Transfers", "DEBIT", "To savings", 5000.0),
+        ];
+
+        let report = build_monthly_report(&rows, "2024-02", "2024-02", 3);
+
+        assert_eq!(report.months[0].totals_excluding_transfers.debits, 100.0);
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+
+use super::monthly::{BucketSummary, MonthlyReport, Totals};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+pub fn render(report: &MonthlyReport, format: ReportFormat) -> Result<String> {
+    match format {
+        ReportFormat::Table => Ok(render_table(report)),
+        ReportFormat::Json => Ok(serde_json::to_string_pretty(report)?),
+        ReportFormat::Csv => Ok(render_csv(report)),
+    }
+}
+
+pub fn render_table(report: &MonthlyReport) -> String {
+    let mut output = String::new();
+
+    if report.months.is_empty() {
+        output.push_str(&format!(
+            "No transactions found between {} and {}\n",
+            report.from_month, report.to_month
+        ));
+        return output;
+    }
+
+    for month in &report.months {
+        output.push_str(&format!("=== {} ===\n", month.month));
+        push_bucket_table(
+            &mut output,
+            &month.buckets,
+            &month.totals_excluding_transfers,
+        );
+
+        for account in &month.accounts {
+            output.push_str(&format!("\n--- Account {} ---\n", account.account_id));
+            push_bucket_table(
+                &mut output,
+                &account.buckets,
+                &account.totals_excluding_transfers,
+            );
+        }
+
+        output.push('\n');
+    }
+
+    output
+}
+
+fn push_bucket_table(output: &mut String, buckets: &[BucketSummary], totals: &Totals) {
+    output.push_str(&format!(
+        "{:<24} {:>12} {:>12} {:>12} {:>12}  {}\n",
+        "Bucket", "Debits", "Credits", "Net", "Change", "Top merchants"
+    ));
+    output.push_str(&format!("{}\n", "-".repeat(104)));
+
+    for bucket in buckets {
+        let change = bucket
+            .debits_change
+            .map(|change| format!("{:+.2}", change))
+            .unwrap_or_else(|| "-".to_string());
+
+        output.push_str(&format!(
+            "{:<24} {:>12.2} {:>12.2} {:>12.2} {:>12}  {}\n",
+            bucket.bucket,
+            bucket.debits,
+            bucket.credits,
+            bucket.net,
+            change,
+            top_merchants_label(bucket)
+        ));
+    }
+
+    output.push_str(&format!("{}\n", "-".repeat(104)));
+    output.push_str(&format!(
+        "{:<24} {:>12.2} {:>12.2} {:>12.2}\n",
+        "Total (excl. transfers)", totals.debits, totals.credits, totals.net
+    ));
+}
+
+pub fn render_csv(report: &MonthlyReport) -> String {
+    let mut output =
+        String::from("month,account_id,bucket,debits,credits,net,debits_change,top_merchants\n");
+
+    for month in &report.months {
+        for bucket in &month.buckets {
+            push_csv_row(&mut output, &month.month, "ALL", bucket);
+        }
+        for account in &month.accounts {
+            for bucket in &account.buckets {
+                push_csv_row(&mut output, &month.month, &account.account_id, bucket);
+            }
+        }
+    }
+
+    output
+}
+
+fn push_csv_row(output: &mut String, month: &str, account_id: &str, bucket: &BucketSummary) {
+    let change = bucket
+        .debits_change
+        .map(|change| format!("{:.2}", change))
+        .unwrap_or_default();
+
+    output.push_str(&format!(
+        "{},{},{},{:.2},{:.2},{:.2},{},{}\n",
+        month,
+        csv_field(account_id),
+        csv_field(&bucket.bucket),
+        bucket.debits,
+        bucket.credits,
+        bucket.net,
+        change,
+        csv_field(&top_merchants_label(bucket))
+    ));
+}
+
+fn top_merchants_label(bucket: &BucketSummary) -> String {
+    bucket
+        .top_merchants
+        .iter()
+        .map(|merchant| format!("{} ({:.2})", merchant.merchant, merchant.debits))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -1,110 +1,717 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use tokio_cron_scheduler::{Job, JobScheduler};
+use tracing::Instrument;
 
+use crate::anomaly;
 use crate::bucket_classifier::BucketClassifier;
+use crate::budgets;
 use crate::clients::InvestecClient;
-use crate::clients::investec::models;
+use crate::clients::investec::{InvestecError, TransactionQuery, models};
+use crate::config::settings::{
+    BUCKET_OTHER, DEFAULT_PROFILE, SCHEDULE_OFF, SchedulesConfig, SyncConfig,
+};
 use crate::db;
+use crate::monitoring;
+use crate::notifications::{ClassifiedTransaction, Event, Notifier};
+use crate::recurring;
+use crate::reports;
+use crate::shutdown::Shutdown;
+use crate::sweeps;
 
-pub async fn start_hourly(
-    client: Arc<InvestecClient>,
-    classifier: Arc<BucketClassifier>,
-    database_url: String,
+/// Days a card event may stay pending before it is treated as never posting.
+const PENDING_CARD_EVENT_DAYS: i32 = 14;
+
+/// Work the scheduler runs, each on its own schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum JobKind {
+    Sync,
+    Balances,
+    Recurring,
+    Reports,
+    Reclassify,
+    Sweeps,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 6] = [
+        JobKind::Sync,
+        JobKind::Balances,
+        JobKind::Recurring,
+        JobKind::Reports,
+        JobKind::Reclassify,
+        JobKind::Sweeps,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Sync => "sync",
+            JobKind::Balances => "balances",
+            JobKind::Recurring => "recurring",
+            JobKind::Reports => "reports",
+            JobKind::Reclassify => "reclassify",
+            JobKind::Sweeps => "sweeps",
+        }
+    }
+
+    pub fn schedule(self, schedules: &SchedulesConfig) -> Option<&str> {
+        match self {
+            JobKind::Sync => schedules.sync.as_deref(),
+            JobKind::Balances => schedules.balances.as_deref(),
+            JobKind::Recurring => schedules.recurring.as_deref(),
+            JobKind::Reports => schedules.reports.as_deref(),
+            JobKind::Reclassify => schedules.reclassify.as_deref(),
+            JobKind::Sweeps => schedules.sweeps.as_deref(),
+        }
+    }
+}
+
+/// Last and next run of a scheduled job, as persisted in `scheduled_jobs`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct JobStatus {
+    pub name: String,
+    pub schedule: String,
+    pub status: Option<String>,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub skipped_runs: i32,
+}
+
+/// What the jobs run against, shared by every run.
+pub struct JobContext {
+    pub clients: Arc<Vec<InvestecClient>>,
+    pub classifier: Arc<BucketClassifier>,
+    pub notifier: Arc<Notifier>,
+    pub sync_config: SyncConfig,
+    pub reclassify_days: u32,
+    pub shutdown: Arc<Shutdown>,
+}
+
+/// The next time `schedule` fires after now.
+pub fn next_run(schedule: &str) -> Option<DateTime<Utc>> {
+    croner::Cron::new(schedule)
+        .with_seconds_required()
+        .with_dom_and_dow()
+        .parse()
+        .ok()?
+        .find_next_occurrence(&Utc::now(), false)
+        .ok()
+}
+
+/// Schedules every job that isn't off and records them in `scheduled_jobs`.
+/// Every run shares `database` and its pool.
+pub async fn start(
+    context: Arc<JobContext>,
+    schedules: &SchedulesConfig,
+    database: Arc<db::Database>,
 ) -> anyhow::Result<JobScheduler> {
     let scheduler = JobScheduler::new().await?;
+    let mut registered = Vec::new();
 
-    let db_url = database_url.clone();
-
-    scheduler
-        .add(Job::new_async("0 0 * * * *", move |_uuid, _l| {
-            let client = Arc::clone(&client);
-            let classifier = Arc::clone(&classifier);
-            let db_url = db_url.clone();
-            Box::pin(async move {
-                tracing::debug!("Scheduler triggered");
-                match db::Database::initialize(&db_url).await {
-                    Ok(database) => {
-                        run_sync(client.as_ref(), classifier.as_ref(), &database).await;
-                    }
-                    Err(e) => tracing::error!("Failed to init DB for scheduled job: {}", e),
-                }
-            })
-        })?)
-        .await?;
+    for kind in JobKind::ALL {
+        let Some(schedule) = kind.schedule(schedules) else {
+            continue;
+        };
+
+        let running = Arc::new(AtomicBool::new(false));
+        let context = Arc::clone(&context);
+        let job_database = Arc::clone(&database);
+        let jitter_secs = schedules.jitter_secs;
+        let job_schedule = schedule.to_string();
+        scheduler
+            .add(Job::new_async(schedule, move |_uuid, _l| {
+                let context = Arc::clone(&context);
+                let running = Arc::clone(&running);
+                let database = Arc::clone(&job_database);
+                let schedule = job_schedule.clone();
+                Box::pin(async move {
+                    run_scheduled(kind, &schedule, jitter_secs, &context, &running, &database)
+                        .await;
+                })
+            })?)
+            .await?;
+
+        tracing::info!(job = kind.as_str(), schedule, "Job scheduled");
+        registered.push((kind.as_str(), schedule, next_run(schedule)));
+    }
 
+    db::register_scheduled_jobs(&database.pool, &registered).await?;
     scheduler.start().await?;
     Ok(scheduler)
 }
 
+/// Clears the job's running flag however the run ends.
+struct RunningGuard<'a>(&'a AtomicBool);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+async fn run_scheduled(
+    kind: JobKind,
+    schedule: &str,
+    jitter_secs: u64,
+    context: &JobContext,
+    running: &AtomicBool,
+    database: &db::Database,
+) {
+    if jitter_secs > 0 {
+        tokio::time::sleep(Duration::from_secs(rand::random_range(0..=jitter_secs))).await;
+    }
+
+    tracing::debug!(job = kind.as_str(), "Scheduler triggered");
+    let Some(_job) = context.shutdown.start_job() else {
+        tracing::debug!(job = kind.as_str(), "Shutting down, not starting");
+        return;
+    };
+    if running.swap(true, Ordering::SeqCst) {
+        tracing::warn!(
+            job = kind.as_str(),
+            "Previous run is still in progress, skipping"
+        );
+        if let Err(e) =
+            db::record_job_skipped(&database.pool, kind.as_str(), next_run(schedule)).await
+        {
+            tracing::warn!(job = kind.as_str(), error = %e, "Failed to record job status");
+        }
+        return;
+    }
+    let _guard = RunningGuard(running);
+
+    let _ = run_recorded(kind, Some(schedule), context, database).await;
+}
+
+/// Runs a job once, recording its status; `schedule` is `None` for a job
+/// that isn't scheduled and is run by hand.
+pub async fn run_recorded(
+    kind: JobKind,
+    schedule: Option<&str>,
+    context: &JobContext,
+    database: &db::Database,
+) -> anyhow::Result<()> {
+    let name = kind.as_str();
+    if let Err(e) =
+        db::record_job_started(&database.pool, name, schedule.unwrap_or(SCHEDULE_OFF)).await
+    {
+        tracing::warn!(job = name, error = %e, "Failed to record job status");
+    }
+
+    let result = run_job(kind, context, database).await;
+    if let Err(e) = &result {
+        tracing::error!(job = name, error = %e, "Job failed");
+    }
+
+    let error = result.as_ref().err().map(|e| e.to_string());
+    if let Err(e) = db::record_job_finished(
+        &database.pool,
+        name,
+        error.as_deref(),
+        schedule.and_then(next_run),
+    )
+    .await
+    {
+        tracing::warn!(job = name, error = %e, "Failed to record job status");
+    }
+
+    result
+}
+
+async fn run_job(
+    kind: JobKind,
+    context: &JobContext,
+    database: &db::Database,
+) -> anyhow::Result<()> {
+    match kind {
+        JobKind::Sync => {
+            run_sync(
+                &context.clients,
+                &context.classifier,
+                database,
+                &context.notifier,
+                &context.sync_config,
+                &context.shutdown,
+            )
+            .await
+        }
+        JobKind::Balances => snapshot_balances(&context.clients, database).await,
+        JobKind::Recurring => check_subscriptions(database, &context.notifier).await,
+        JobKind::Reports => send_monthly_report(database, &context.notifier).await,
+        JobKind::Reclassify => {
+            reclassify_other(
+                database,
+                &context.classifier,
+                context.reclassify_days,
+                &context.shutdown,
+            )
+            .await
+        }
+        JobKind::Sweeps => plan_sweeps(database, &context.notifier).await,
+    }
+}
+
+/// Syncs every profile while holding the sync lease, so at most one sync
+/// runs at a time across all processes sharing the database.
+#[tracing::instrument(skip_all)]
 pub async fn run_sync(
-    client: &InvestecClient,
+    clients: &[InvestecClient],
     classifier: &BucketClassifier,
     database: &db::Database,
-) {
+    notifier: &Notifier,
+    sync_config: &SyncConfig,
+    shutdown: &Shutdown,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let Some(lease) = db::Lease::try_acquire_sync(&database.pool).await? else {
+        tracing::info!("Another sync is running, skipping");
+        monitoring::sync_finished("skipped", started.elapsed());
+        return Ok(());
+    };
+    let result = sync_all(
+        clients,
+        classifier,
+        database,
+        notifier,
+        sync_config,
+        shutdown,
+    )
+    .await;
+    if let Err(e) = lease.release().await {
+        tracing::warn!(error = %e, "Failed to release the sync lease");
+    }
+
+    let outcome = if result.is_ok() { "success" } else { "failure" };
+    monitoring::sync_finished(outcome, started.elapsed());
+    result
+}
+
+async fn sync_all(
+    clients: &[InvestecClient],
+    classifier: &BucketClassifier,
+    database: &db::Database,
+    notifier: &Notifier,
+    sync_config: &SyncConfig,
+    shutdown: &Shutdown,
+) -> anyhow::Result<()> {
     tracing::info!("Starting transaction sync");
 
+    let mut new_transactions = Vec::new();
+    let mut synced_any = false;
+    for client in clients {
+        if shutdown.is_requested() {
+            break;
+        }
+        if let Some(classified) = sync_profile(
+            client,
+            classifier,
+            database,
+            notifier,
+            sync_config,
+            shutdown,
+        )
+        .await
+        {
+            synced_any = true;
+            new_transactions.extend(classified);
+        }
+    }
+
+    if !synced_any {
+        anyhow::bail!("No profile could be synced");
+    }
+
+    if !new_transactions.is_empty() {
+        notifier
+            .notify(Event::new_transactions(&new_transactions))
+            .await;
+        check_anomalies(&database.pool, notifier, &new_transactions).await;
+    }
+
+    expire_pending_card_events(database).await;
+    check_budgets(database, notifier).await;
+    record_bucket_spend(database, &classifier.buckets).await;
+
+    // Every stored transaction is committed with its classification, so the
+    // next sync picks up where this one stopped.
+    if shutdown.is_requested() {
+        anyhow::bail!("Sync interrupted by shutdown");
+    }
+    Ok(())
+}
+
+/// Names failures after the profile once more than the default is configured.
+fn failure_context(client: &InvestecClient, context: &str) -> String {
+    if client.profile() == DEFAULT_PROFILE {
+        context.to_string()
+    } else {
+        format!("{} {}", client.profile(), context)
+    }
+}
+
+/// Syncs today's transactions of one profile. Returns `None` when the profile
+/// couldn't be synced at all.
+#[tracing::instrument(skip_all, fields(profile = client.profile()))]
+async fn sync_profile(
+    client: &InvestecClient,
+    classifier: &BucketClassifier,
+    database: &db::Database,
+    notifier: &Notifier,
+    sync_config: &SyncConfig,
+    shutdown: &Shutdown,
+) -> Option<Vec<ClassifiedTransaction>> {
+    let profile = client.profile();
+
     match client.get_accounts().await {
         Ok(accounts) => {
             if accounts.is_empty() {
-                tracing::warn!("No accounts found");
-                return;
+                tracing::warn!(profile, "No accounts found");
+                return None;
             }
 
-            let mut total_transactions = 0;
-            let mut new_transactions = 0;
+            sync_accounts(
+                client,
+                &accounts,
+                notifier,
+                sync_config,
+                shutdown,
+                |transactions| async move {
+                    process_transactions(&transactions, profile, classifier, database, shutdown)
+                        .await
+                },
+            )
+            .await
+        }
+        Err(e @ InvestecError::CircuitOpen(_)) => {
+            tracing::warn!(profile, error = %e, "Skipping sync");
+            None
+        }
+        Err(e) => {
+            tracing::error!(profile, error = %e, "Failed to get accounts");
+            notifier
+                .notify(Event::sync_failed(
+                    &failure_context(client, "accounts"),
+                    &e.to_string(),
+                ))
+                .await;
+            None
+        }
+    }
+}
 
-            for account in &accounts {
-                let today = Utc::now().date_naive();
-                let tomorrow = today + chrono::Duration::days(1);
-                let from_date = today.format("%Y-%m-%d").to_string();
-                let to_date: String = tomorrow.format("%Y-%m-%d").to_string();
+/// Fetches today's transactions of each account and hands them to `store`.
+/// Rejected credentials stop the loop, but what earlier accounts stored is
+/// still returned so it gets notified and scored. `None` when they were
+/// rejected before any account was fetched.
+async fn sync_accounts<F>(
+    client: &InvestecClient,
+    accounts: &[models::Account],
+    notifier: &Notifier,
+    sync_config: &SyncConfig,
+    shutdown: &Shutdown,
+    mut store: impl FnMut(Vec<models::Transaction>) -> F,
+) -> Option<Vec<ClassifiedTransaction>>
+where
+    F: Future<Output = Vec<ClassifiedTransaction>>,
+{
+    let profile = client.profile();
+    let mut total_transactions = 0;
+    let mut new_transactions = Vec::new();
+    let mut fetched_any = false;
 
-                match client
-                    .get_transactions(&account.account_id, &from_date, &to_date)
-                    .await
-                {
-                    Ok(transactions_response) => {
-                        let count = transactions_response.transactions.len();
-                        total_transactions += count;
-
-                        if count > 0 {
-                            let new_count = process_transactions(
-                                &transactions_response.transactions,
-                                classifier,
-                                database,
-                            )
-                            .await;
-                            new_transactions += new_count;
-                        }
-                    }
-                    Err(e) => tracing::error!(
-                        account_id = %account.account_id,
-                        error = %e,
-                        "Failed to get transactions"
-                    ),
+    for account in accounts {
+        if shutdown.is_requested() {
+            tracing::info!(profile, "Shutting down, stopping sync");
+            break;
+        }
+        let today = Utc::now().date_naive();
+        let tomorrow = today + chrono::Duration::days(1);
+        let mut query = TransactionQuery::new()
+            .since(today.format("%Y-%m-%d").to_string())
+            .until(tomorrow.format("%Y-%m-%d").to_string())
+            .include_pending(sync_config.include_pending);
+        if let Some(transaction_type) = sync_config.server_filter() {
+            query = query.transaction_type(transaction_type);
+        }
+
+        let fetch = tracing::info_span!("fetch_transactions", account_id = %account.account_id);
+        match client
+            .get_transactions(&account.account_id, &query)
+            .instrument(fetch)
+            .await
+        {
+            Ok(transactions_response) => {
+                fetched_any = true;
+                let transactions: Vec<_> = transactions_response
+                    .transactions
+                    .into_iter()
+                    .filter(|tx| sync_config.ingests(tx.transaction_type.as_deref()))
+                    .collect();
+                let count = transactions.len();
+                total_transactions += count;
+
+                if count > 0 {
+                    new_transactions.extend(store(transactions).await);
                 }
             }
+            Err(e @ InvestecError::Auth { .. }) => {
+                // Every later call would fail the same way.
+                tracing::error!(profile, error = %e, "Investec credentials rejected, stopping sync");
+                monitoring::sync_account_failed(profile, &account.account_id);
+                notifier
+                    .notify(Event::sync_failed(
+                        &failure_context(client, "authentication"),
+                        &e.to_string(),
+                    ))
+                    .await;
+                if !fetched_any {
+                    return None;
+                }
+                break;
+            }
+            Err(e @ InvestecError::CircuitOpen(_)) => {
+                tracing::warn!(profile, error = %e, "Stopping sync");
+                break;
+            }
+            Err(InvestecError::NotFound { .. }) => {
+                tracing::warn!(
+                    account_id = %account.account_id,
+                    "Account no longer exists, skipping"
+                );
+            }
+            Err(e) => {
+                tracing::error!(
+                    account_id = %account.account_id,
+                    error = %e,
+                    "Failed to get transactions"
+                );
+                monitoring::sync_account_failed(profile, &account.account_id);
+                notifier
+                    .notify(Event::sync_failed(
+                        &format!("account {}", account.account_id),
+                        &e.to_string(),
+                    ))
+                    .await;
+            }
+        }
+    }
+
+    tracing::info!(
+        profile,
+        total = total_transactions,
+        new = new_transactions.len(),
+        "Sync complete"
+    );
+
+    Some(new_transactions)
+}
+
+/// Scores newly stored transactions and alerts on the anomalous ones.
+pub async fn check_anomalies(
+    pool: &sqlx::PgPool,
+    notifier: &Notifier,
+    transactions: &[ClassifiedTransaction],
+) {
+    match anomaly::evaluate_new_transactions(pool, transactions, Utc::now().date_naive()).await {
+        Ok(anomalies) => {
+            for anomaly in anomalies {
+                let kinds: Vec<&str> = anomaly.kinds.iter().map(|kind| kind.as_str()).collect();
+                tracing::warn!(
+                    account_id = %anomaly.transaction.account_id,
+                    uuid = ?anomaly.transaction.uuid,
+                    anomalies = ?kinds,
+                    "Anomalous transaction"
+                );
+                notifier.notify(Event::anomaly(&anomaly)).await;
+            }
+        }
+        Err(e) => tracing::error!(error = %e, "Failed to score transactions for anomalies"),
+    }
+}
+
+/// Drops card events that never posted (reversals, declined holds) so they
+/// stop counting towards budgets and sweeps.
+async fn expire_pending_card_events(database: &db::Database) {
+    match db::delete_stale_card_events(&database.pool, PENDING_CARD_EVENT_DAYS).await {
+        Ok(0) => {}
+        Ok(deleted) => tracing::info!(deleted, "Expired card events that never posted"),
+        Err(e) => tracing::error!(error = %e, "Failed to expire pending card events"),
+    }
+}
+
+async fn plan_sweeps(database: &db::Database, notifier: &Notifier) -> anyhow::Result<()> {
+    match sweeps::plan_sweeps(&database.pool, Utc::now().date_naive()).await? {
+        Some(plan) => {
+            let items = db::list_sweep_plan_items(&database.pool, plan.id).await?;
+            tracing::info!(plan_id = plan.id, items = items.len(), "Sweep plan created");
+            notifier.notify(Event::sweep_planned(&plan, &items)).await;
+        }
+        None => tracing::debug!("Nothing to sweep"),
+    }
+    Ok(())
+}
+
+/// Publishes this month's spend per bucket as metrics.
+async fn record_bucket_spend(database: &db::Database, buckets: &[String]) {
+    let today = Utc::now().date_naive();
+    let (month_start, month_end) = budgets::BudgetPeriod::Monthly.bounds(today);
+    match db::spend_by_bucket_between(&database.pool, month_start, month_end).await {
+        Ok(spend) => monitoring::set_bucket_spend(buckets, &spend),
+        Err(e) => tracing::warn!(error = %e, "Failed to compute bucket spend"),
+    }
+}
+
+async fn check_budgets(database: &db::Database, notifier: &Notifier) {
+    match budgets::evaluate_budgets(&database.pool, Utc::now().date_naive()).await {
+        Ok(alerts) => {
+            for alert in alerts {
+                tracing::warn!(
+                    bucket = %alert.bucket,
+                    period = %alert.period,
+                    period_start = %alert.period_start,
+                    threshold = alert.threshold,
+                    spent = alert.spent,
+                    limit = alert.limit,
+                    "Budget threshold crossed"
+                );
+                notifier.notify(Event::budget_threshold(&alert)).await;
+            }
+        }
+        Err(e) => tracing::error!(error = %e, "Failed to evaluate budgets"),
+    }
+}
+
+async fn check_subscriptions(database: &db::Database, notifier: &Notifier) -> anyhow::Result<()> {
+    let flags = recurring::evaluate_subscriptions(&database.pool, Utc::now().date_naive()).await?;
+    for flag in flags {
+        tracing::warn!(
+            account_id = %flag.account_id,
+            description = %flag.description,
+            flag = flag.kind.as_str(),
+            "Subscription changed"
+        );
+        notifier.notify(Event::subscription_alert(&flag)).await;
+    }
+    Ok(())
+}
+
+/// Records the balance of every account of every profile. Accounts that fail
+/// are reported together after the rest are recorded.
+async fn snapshot_balances(
+    clients: &[InvestecClient],
+    database: &db::Database,
+) -> anyhow::Result<()> {
+    let mut recorded = 0;
+    let mut failures = Vec::new();
+
+    for client in clients {
+        let accounts = match client.get_accounts().await {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                failures.push(format!("{}: {}", failure_context(client, "accounts"), e));
+                continue;
+            }
+        };
+
+        for account in accounts {
+            match client.get_account_balance(&account.account_id).await {
+                Ok(balance) => {
+                    db::insert_balance_snapshot(&database.pool, client.profile(), &balance).await?;
+                    recorded += 1;
+                }
+                Err(e) => failures.push(format!("account {}: {}", account.account_id, e)),
+            }
+        }
+    }
+
+    tracing::info!(recorded, failed = failures.len(), "Balances recorded");
+    if !failures.is_empty() {
+        anyhow::bail!("{}", failures.join("; "));
+    }
+    Ok(())
+}
 
-            tracing::info!(
-                total = total_transactions,
-                new = new_transactions,
-                "Sync complete"
+/// Sends last month's report across all profiles to the notification sinks.
+async fn send_monthly_report(database: &db::Database, notifier: &Notifier) -> anyhow::Result<()> {
+    let month = reports::monthly::previous_month(&Utc::now().format("%Y-%m").to_string())?;
+    let report =
+        reports::generate_monthly_report(&database.pool, &month, &month, None, None, 3).await?;
+    let table = reports::render::render_table(&report);
+
+    notifier
+        .notify(Event::monthly_report(&report, &table))
+        .await;
+    tracing::info!(month = %month, "Monthly report sent");
+    Ok(())
+}
+
+/// Gives transactions from the last `days` that ended up in "Other" another
+/// chance, e.g. after a provider outage or a change of buckets.
+async fn reclassify_other(
+    database: &db::Database,
+    classifier: &BucketClassifier,
+    days: u32,
+    shutdown: &Shutdown,
+) -> anyhow::Result<()> {
+    let since = Utc::now().date_naive() - chrono::Duration::days(i64::from(days));
+    let transactions = db::list_transactions_in_bucket(&database.pool, BUCKET_OTHER, since).await?;
+
+    let mut moved = 0;
+    for (id, transaction) in &transactions {
+        if shutdown.is_requested() {
+            anyhow::bail!(
+                "Reclassification interrupted by shutdown after {} moves",
+                moved
             );
         }
-        Err(e) => tracing::error!(error = %e, "Failed to get accounts"),
+        let bucket = classifier
+            .classify_transaction_with_fallback(transaction)
+            .await?;
+        if bucket != BUCKET_OTHER {
+            db::set_annotation_bucket(&database.pool, *id, &bucket).await?;
+            tracing::info!(id, bucket = %bucket, "Transaction reclassified");
+            moved += 1;
+        }
     }
+
+    tracing::info!(
+        checked = transactions.len(),
+        moved,
+        "Reclassification complete"
+    );
+    Ok(())
 }
 
 pub async fn process_transactions(
     transactions: &[models::Transaction],
+    profile: &str,
     classifier: &BucketClassifier,
     database: &db::Database,
-) -> usize {
-    let mut new_count = 0;
+    shutdown: &Shutdown,
+) -> Vec<ClassifiedTransaction> {
+    let mut classified = Vec::new();
 
     for transaction in transactions.iter() {
+        if shutdown.is_requested() {
+            break;
+        }
+        let pending_key = pending_key(transaction);
+        if let Some(key) = &pending_key {
+            match db::find_transaction_id_by_card_event(&database.pool, key).await {
+                Ok(None) => {}
+                Ok(Some(_)) | Err(_) => continue,
+            }
+            // Already stored when its card event arrived.
+            match db::find_pending_match(&database.pool, transaction).await {
+                Ok(None) => {}
+                Ok(Some(_)) | Err(_) => continue,
+            }
+        }
         if let Some(uuid) = &transaction.uuid {
             match db::find_transaction_id_by_uuid(&database.pool, uuid).await {
                 Ok(Some(_)) => {
@@ -115,6 +722,19 @@ pub async fn process_transactions(
                     continue;
                 }
             }
+
+            // Already classified when its card event arrived.
+            match db::reconcile_pending_transaction(&database.pool, transaction).await {
+                Ok(Some(id)) => {
+                    tracing::debug!(id, uuid = %uuid, "Reconciled pending card event");
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::error!(uuid = %uuid, error = %e, "Failed to reconcile transaction");
+                    continue;
+                }
+            }
         }
 
         let bucket = match classifier
@@ -127,13 +747,155 @@ pub async fn process_transactions(
             }
         };
 
-        match db::insert_tx_and_annotation(&database.pool, transaction, &bucket, None).await {
-            Ok(_) => {
-                new_count += 1;
+        let inserted = match &pending_key {
+            Some(key) => {
+                db::insert_card_event(&database.pool, profile, key, transaction, &bucket, None)
+                    .await
+            }
+            None => {
+                db::insert_tx_and_annotation(&database.pool, profile, transaction, &bucket, None)
+                    .await
+            }
+        };
+        match inserted {
+            Ok(Some(id)) => {
+                monitoring::transaction_inserted("sync");
+                classified.push(ClassifiedTransaction {
+                    id,
+                    uuid: transaction.uuid.clone(),
+                    account_id: transaction.account_id.clone(),
+                    description: transaction.description.clone(),
+                    amount: transaction.amount,
+                    bucket,
+                });
+            }
+            Ok(None) => {
+                tracing::debug!(uuid = ?transaction.uuid, "Transaction stored concurrently, skipping");
+            }
+            Err(e) => {
+                tracing::error!(uuid = ?transaction.uuid, error = %e, "Failed to store transaction");
             }
-            Err(_) => {}
         }
     }
 
-    new_count
+    classified
+}
+
+/// Identifies a pending transaction across syncs, since Investec only assigns
+/// a uuid once it posts. Stored like a card event so the posted transaction
+/// is reconciled into it.
+fn pending_key(transaction: &models::Transaction) -> Option<String> {
+    if transaction.uuid.is_some() || !transaction.status.eq_ignore_ascii_case("PENDING") {
+        return None;
+    }
+
+    Some(format!(
+        "pending:{}:{}:{:.2}:{}",
+        transaction.account_id,
+        transaction.transaction_date.as_deref().unwrap_or_default(),
+        transaction.amount,
+        transaction.description
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::settings::Config;
+    use crate::mock_investec::{self, MockState, fixtures};
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_pending_key_only_for_pending_transactions_without_uuid() {
+        let mut transaction = models::Transaction {
+            account_id: "acc".to_string(),
+            type_: "DEBIT".to_string(),
+            transaction_type: Some("CardPurchases".to_string()),
+            status: "PENDING".to_string(),
+            description: "PICK N PAY".to_string(),
+            card_number: None,
+            posted_order: None,
+            posting_date: None,
+            value_date: None,
+            action_date: None,
+            transaction_date: Some("2024-03-05".to_string()),
+            amount: 412.3,
+            running_balance: None,
+            uuid: None,
+        };
+        assert_eq!(
+            pending_key(&transaction).as_deref(),
+            Some("pending:acc:2024-03-05:412.30:PICK N PAY")
+        );
+
+        transaction.uuid = Some("uuid-1".to_string());
+        assert_eq!(pending_key(&transaction), None);
+
+        transaction.uuid = None;
+        transaction.status = "POSTED".to_string();
+        assert_eq!(pending_key(&transaction), None);
+    }
+
+    #[test]
+    fn test_job_schedules() {
+        let schedules = SchedulesConfig::default();
+        let scheduled: Vec<&str> = JobKind::ALL
+            .iter()
+            .filter(|kind| kind.schedule(&schedules).is_some())
+            .map(|kind| kind.as_str())
+            .collect();
+        assert_eq!(scheduled, ["sync", "balances", "recurring", "sweeps"]);
+
+        let next = next_run("0 0 * * * *").unwrap();
+        assert!(next > Utc::now());
+        assert_eq!(next.format("%M:%S").to_string(), "00:00");
+        assert!(next_run("every hour").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sync_keeps_stored_transactions_when_credentials_are_rejected() {
+        let state = Arc::new(MockState::new(fixtures::Fixtures::seed(
+            Utc::now().date_naive(),
+        )));
+        state.fail_account(fixtures::SAVINGS_ACCOUNT_ID, StatusCode::UNAUTHORIZED);
+        let (addr, _) = mock_investec::spawn("127.0.0.1:0", state).await.unwrap();
+        let mut config = Config::for_tests();
+        config.investec.base_url = format!("http://{}", addr);
+        let client = InvestecClient::new(config).unwrap();
+        let accounts = client.get_accounts().await.unwrap();
+
+        let mut stored = Vec::new();
+        let new_transactions = sync_accounts(
+            &client,
+            &accounts,
+            &Notifier::new(1, Duration::ZERO),
+            &SyncConfig::default(),
+            &Shutdown::new(),
+            |transactions| {
+                stored.push(transactions[0].account_id.clone());
+                let classified: Vec<_> = transactions
+                    .iter()
+                    .map(|tx| ClassifiedTransaction {
+                        id: 0,
+                        uuid: tx.uuid.clone(),
+                        account_id: tx.account_id.clone(),
+                        description: tx.description.clone(),
+                        amount: tx.amount,
+                        bucket: BUCKET_OTHER.to_string(),
+                    })
+                    .collect();
+                async move { classified }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(stored, [fixtures::CHEQUE_ACCOUNT_ID]);
+        assert_eq!(new_transactions.len(), 2);
+        assert!(
+            new_transactions
+                .iter()
+                .all(|tx| tx.account_id == fixtures::CHEQUE_ACCOUNT_ID)
+        );
+    }
 }
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    requested: bool,
+    running: usize,
+}
+
+/// Coordinates shutdown between `main` and the jobs: once requested no new
+/// job starts, running ones stop at their next safe point, and `main` waits
+/// for them to finish.
+#[derive(Debug, Default)]
+pub struct Shutdown {
+    state: watch::Sender<State>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&self) {
+        self.state.send_modify(|state| state.requested = true);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.state.borrow().requested
+    }
+
+    /// Resolves once shutdown is requested.
+    pub async fn requested(&self) {
+        let mut state = self.state.subscribe();
+        let _ = state.wait_for(|state| state.requested).await;
+    }
+
+    /// Marks a job as running until the guard is dropped; `None` once
+    /// shutdown is requested.
+    pub fn start_job(&self) -> Option<JobGuard<'_>> {
+        let started = self.state.send_if_modified(|state| {
+            if state.requested {
+                return false;
+            }
+            state.running += 1;
+            true
+        });
+
+        started.then(|| JobGuard(self))
+    }
+
+    pub fn running_jobs(&self) -> usize {
+        self.state.borrow().running
+    }
+
+    /// Waits up to `timeout` for running jobs to finish. Returns whether they
+    /// all did.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let mut state = self.state.subscribe();
+        tokio::time::timeout(timeout, state.wait_for(|state| state.running == 0))
+            .await
+            .is_ok()
+    }
+}
+
+pub struct JobGuard<'a>(&'a Shutdown);
+
+impl Drop for JobGuard<'_> {
+    fn drop(&mut self) {
+        self.0.state.send_modify(|state| state.running -= 1);
+    }
+}
+
+/// Waits for SIGINT (ctrl-c) or, on Unix, SIGTERM as sent by `docker stop`.
+/// Returns the name of the signal.
+pub async fn signal() -> anyhow::Result<&'static str> {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                Ok("SIGINT")
+            }
+            _ = terminate.recv() => Ok("SIGTERM"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await?;
+        Ok("SIGINT")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_waits_for_running_jobs() {
+        let shutdown = Shutdown::new();
+        let job = shutdown.start_job().unwrap();
+
+        shutdown.request();
+        assert!(shutdown.start_job().is_none());
+        assert!(!shutdown.wait_idle(Duration::from_millis(10)).await);
+        assert_eq!(shutdown.running_jobs(), 1);
+
+        drop(job);
+        assert!(shutdown.wait_idle(Duration::from_millis(10)).await);
+    }
+}
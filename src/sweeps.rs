@@ -0,0 +1,369 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use sqlx::PgPool;
+
+use crate::budgets::BudgetPeriod;
+use crate::clients::InvestecClient;
+use crate::clients::investec::models::TransferInstruction;
+use crate::db;
+
+pub const STATUS_EXECUTED: &str = "executed";
+pub const STATUS_FAILED: &str = "failed";
+/// The transfer may or may not have gone through, e.g. it timed out.
+pub const STATUS_UNKNOWN: &str = "unknown";
+
+/// Investec truncates longer statement references.
+const MAX_REFERENCE_LEN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SweepKind {
+    /// Sweep what's left of the bucket's monthly budget
+    Underspend,
+    /// Round every purchase in the bucket up and sweep the difference
+    RoundUp,
+}
+
+impl SweepKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SweepKind::Underspend => "underspend",
+            SweepKind::RoundUp => "round_up",
+        }
+    }
+}
+
+impl fmt::Display for SweepKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SweepKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "underspend" => Ok(SweepKind::Underspend),
+            "round_up" => Ok(SweepKind::RoundUp),
+            other => Err(anyhow::anyhow!("Unknown sweep kind: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SweepRule {
+    pub id: i32,
+    pub kind: String,
+    pub bucket: String,
+    pub from_account_id: String,
+    pub to_account_id: String,
+    pub round_to: Option<f64>,
+}
+
+/// A transfer a rule wants made for a period, before it is stored in a plan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepInstruction {
+    pub rule_id: i32,
+    pub from_account_id: String,
+    pub to_account_id: String,
+    pub amount: f64,
+    pub reference: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SweepPlan {
+    pub id: i32,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub approved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SweepPlanItem {
+    pub id: i32,
+    pub from_account_id: String,
+    pub to_account_id: String,
+    pub amount: f64,
+    pub reference: String,
+    pub reason: String,
+    pub status: String,
+    pub payment_reference: Option<String>,
+    pub error: Option<String>,
+}
+
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// The difference between `amount` and the next multiple of `nearest`;
+/// zero when `amount` already is one.
+pub fn round_up_difference(amount: f64, nearest: f64) -> f64 {
+    if nearest <= 0.0 {
+        return 0.0;
+    }
+
+    let amount = round_cents(amount.abs());
+    let rounded = (amount / nearest).ceil() * nearest;
+    round_cents(rounded - amount)
+}
+
+/// The first and last-plus-one day of the month before the one containing
+/// `today`, which is the period a sweep run settles.
+pub fn previous_period(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let (this_month, _) = BudgetPeriod::Monthly.bounds(today);
+    BudgetPeriod::Monthly.bounds(this_month - Days::new(1))
+}
+
+fn reference(rule: &SweepRule) -> String {
+    format!("Sweep {}", rule.bucket)
+        .chars()
+        .take(MAX_REFERENCE_LEN)
+        .collect()
+}
+
+/// Works out what each rule would transfer for `[period_start, period_end)`.
+/// Rules with nothing to sweep produce no instruction.
+pub async fn compute_instructions(
+    pool: &PgPool,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+) -> Result<Vec<SweepInstruction>> {
+    let budgets = db::list_budgets(pool).await?;
+    let mut instructions = Vec::new();
+
+    for rule in db::list_sweep_rules(pool).await? {
+        let (amount, reason) = match SweepKind::from_str(&rule.kind)? {
+            SweepKind::Underspend => {
+                let Some(budget) = budgets.iter().find(|budget| {
+                    budget.bucket == rule.bucket && budget.period == BudgetPeriod::Monthly.as_str()
+                }) else {
+                    tracing::warn!(
+                        rule_id = rule.id,
+                        bucket = %rule.bucket,
+                        "Underspend sweep has no monthly budget, skipping"
+                    );
+                    continue;
+                };
+                let spent =
+                    db::bucket_spend_between(pool, &rule.bucket, period_start, period_end).await?;
+                (
+                    round_cents(budget.amount - spent),
+                    format!(
+                        "{} underspend: budget {:.2}, spent {:.2}",
+                        rule.bucket, budget.amount, spent
+                    ),
+                )
+            }
+            SweepKind::RoundUp => {
+                let nearest = rule.round_to.unwrap_or_default();
+                let debits = db::bucket_debits_between(
+                    pool,
+                    &rule.bucket,
+                    &rule.from_account_id,
+                    period_start,
+                    period_end,
+                )
+                .await?;
+                let total: f64 = debits
+                    .iter()
+                    .map(|amount| round_up_difference(*amount, nearest))
+                    .sum();
+                (
+                    round_cents(total),
+                    format!(
+                        "{} round-up to {:.2} on {} purchase(s)",
+                        rule.bucket,
+                        nearest,
+                        debits.len()
+                    ),
+                )
+            }
+        };
+
+        if amount <= 0.0 {
+            continue;
+        }
+
+        instructions.push(SweepInstruction {
+            rule_id: rule.id,
+            from_account_id: rule.from_account_id.clone(),
+            to_account_id: rule.to_account_id.clone(),
+            amount,
+            reference: reference(&rule),
+            reason,
+        });
+    }
+
+    Ok(instructions)
+}
+
+/// Computes and stores the plan for the period before `today`. Returns `None`
+/// when there is nothing to sweep or the period was already planned.
+pub async fn plan_sweeps(pool: &PgPool, today: NaiveDate) -> Result<Option<SweepPlan>> {
+    let (period_start, period_end) = previous_period(today);
+    plan_sweeps_for(pool, period_start, period_end).await
+}
+
+pub async fn plan_sweeps_for(
+    pool: &PgPool,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+) -> Result<Option<SweepPlan>> {
+    let instructions = compute_instructions(pool, period_start, period_end).await?;
+    if instructions.is_empty() {
+        return Ok(None);
+    }
+
+    match db::insert_sweep_plan(pool, period_start, period_end, &instructions).await? {
+        Some(plan_id) => db::find_sweep_plan(pool, plan_id).await,
+        None => Ok(None),
+    }
+}
+
+/// Executes every item of the plan that hasn't been transferred yet, so a
+/// partially failed plan can be approved again. Items whose outcome is
+/// unknown are skipped until confirmed with [`confirm_item`], and the plan is
+/// leased so concurrent approvals can't send the same transfer twice.
+pub async fn approve_plan(
+    pool: &PgPool,
+    client: &InvestecClient,
+    plan_id: i32,
+) -> Result<Vec<SweepPlanItem>> {
+    let Some(lease) = db::Lease::try_acquire_sweep_plan(pool, plan_id).await? else {
+        anyhow::bail!(
+            "Sweep plan {} is being approved by another process",
+            plan_id
+        );
+    };
+    let result = execute_plan(pool, client, plan_id).await;
+    if let Err(e) = lease.release().await {
+        tracing::warn!(plan_id, error = %e, "Failed to release the sweep plan lease");
+    }
+
+    result
+}
+
+async fn execute_plan(
+    pool: &PgPool,
+    client: &InvestecClient,
+    plan_id: i32,
+) -> Result<Vec<SweepPlanItem>> {
+    let Some(plan) = db::find_sweep_plan(pool, plan_id).await? else {
+        anyhow::bail!("Sweep plan {} not found", plan_id);
+    };
+    if plan.status == STATUS_EXECUTED {
+        anyhow::bail!("Sweep plan {} has already been executed", plan_id);
+    }
+
+    let mut all_executed = true;
+    for item in db::list_sweep_plan_items(pool, plan_id).await? {
+        match item.status.as_str() {
+            STATUS_EXECUTED => continue,
+            STATUS_UNKNOWN => {
+                all_executed = false;
+                tracing::warn!(
+                    item_id = item.id,
+                    "Sweep transfer outcome is unknown, skipping until confirmed"
+                );
+                continue;
+            }
+            _ => {}
+        }
+
+        // Marked unknown while in flight so a crash can't lead to a resend.
+        db::set_sweep_item_result(
+            pool,
+            item.id,
+            STATUS_UNKNOWN,
+            None,
+            Some("Approval stopped while the transfer was in flight"),
+        )
+        .await?;
+
+        let transfer = TransferInstruction::new(
+            &item.to_account_id,
+            item.amount,
+            &item.reference,
+            &item.reference,
+        );
+
+        match client.transfer(&item.from_account_id, &[transfer]).await {
+            Ok(results) => {
+                let payment_reference = results
+                    .first()
+                    .map(|result| result.payment_reference_number.as_str());
+                db::set_sweep_item_result(pool, item.id, STATUS_EXECUTED, payment_reference, None)
+                    .await?;
+            }
+            Err(e) => {
+                all_executed = false;
+                let status = if e.outcome_unknown() {
+                    STATUS_UNKNOWN
+                } else {
+                    STATUS_FAILED
+                };
+                tracing::error!(item_id = item.id, status, error = %e, "Sweep transfer failed");
+                db::set_sweep_item_result(pool, item.id, status, None, Some(&e.to_string()))
+                    .await?;
+            }
+        }
+    }
+
+    let status = if all_executed {
+        STATUS_EXECUTED
+    } else {
+        STATUS_FAILED
+    };
+    db::set_sweep_plan_status(pool, plan_id, status).await?;
+
+    db::list_sweep_plan_items(pool, plan_id).await
+}
+
+/// Records the outcome of a transfer whose outcome was unknown, after
+/// checking the account: `executed` if the money moved, otherwise `failed` so
+/// the next approval retries it.
+pub async fn confirm_item(pool: &PgPool, item_id: i32, executed: bool) -> Result<()> {
+    let status = if executed {
+        STATUS_EXECUTED
+    } else {
+        STATUS_FAILED
+    };
+    let Some(plan_id) = db::confirm_sweep_item(pool, item_id, status).await? else {
+        anyhow::bail!("Sweep transfer {} doesn't exist or isn't unknown", item_id);
+    };
+    db::complete_sweep_plan(pool, plan_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_up_difference() {
+        assert_eq!(round_up_difference(87.5, 10.0), 2.5);
+        assert_eq!(round_up_difference(-87.5, 10.0), 2.5);
+        assert_eq!(round_up_difference(90.0, 10.0), 0.0);
+        assert_eq!(round_up_difference(12.34, 5.0), 2.66);
+        assert_eq!(round_up_difference(12.34, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_previous_period() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        assert_eq!(
+            previous_period(date("2024-03-01")),
+            (date("2024-02-01"), date("2024-03-01"))
+        );
+        assert_eq!(
+            previous_period(date("2024-01-15")),
+            (date("2023-12-01"), date("2024-01-01"))
+        );
+    }
+}
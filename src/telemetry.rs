@@ -0,0 +1,105 @@
+//! Logging and OpenTelemetry trace export.
+//!
+//! Logs go to stdout as text or JSON. With an OTLP endpoint configured, spans
+//! are also exported over OTLP/HTTP, e.g. to a local Jaeger.
+
+use anyhow::Result;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+use crate::config::settings::{LogFormat, TelemetryConfig};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Flushes exported spans on shutdown.
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+/// Installs the global subscriber. `RUST_LOG` filters both logs and spans.
+pub fn init(config: &TelemetryConfig) -> Result<Telemetry> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let mut layers: Vec<BoxedLayer> = vec![match config.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    }];
+
+    let mut provider = None;
+    if let Some(endpoint) = &config.otlp_endpoint {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(traces_url(endpoint))
+            .build()?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(config.service_name.clone())
+                    .build(),
+            )
+            .build();
+
+        let tracer = tracer_provider.tracer(config.service_name.clone());
+        layers.push(tracing_opentelemetry::layer().with_tracer(tracer).boxed());
+        provider = Some(tracer_provider);
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .try_init()?;
+
+    if let Some(endpoint) = &config.otlp_endpoint {
+        tracing::info!(endpoint = %endpoint, "Exporting traces over OTLP");
+    }
+
+    Ok(Telemetry { provider })
+}
+
+/// The traces path under an OTLP base URL, as `OTEL_EXPORTER_OTLP_ENDPOINT`
+/// is defined.
+fn traces_url(endpoint: &str) -> String {
+    let base = endpoint.trim_end_matches('/');
+    if base.ends_with("/v1/traces") {
+        base.to_string()
+    } else {
+        format!("{}/v1/traces", base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(
+            traces_url("http://localhost:4318"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://collector/otlp/"),
+            "http://collector/otlp/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://localhost:4318/v1/traces"),
+            "http://localhost:4318/v1/traces"
+        );
+    }
+}